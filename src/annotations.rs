@@ -0,0 +1,177 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    book::Book,
+    storage::{self, Key},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Color {
+    Yellow,
+    Green,
+    Blue,
+    Pink,
+}
+
+impl Color {
+    pub const ALL: [Color; 4] = [Color::Yellow, Color::Green, Color::Blue, Color::Pink];
+
+    /// Classes for highlighted text.
+    pub fn class(self) -> &'static str {
+        match self {
+            Color::Yellow => "bg-amber-300/50 text-current",
+            Color::Green => "bg-emerald-300/50 text-current",
+            Color::Blue => "bg-sky-300/50 text-current",
+            Color::Pink => "bg-rose-300/50 text-current",
+        }
+    }
+
+    /// Classes for a color picker swatch.
+    pub fn swatch(self) -> &'static str {
+        match self {
+            Color::Yellow => "bg-amber-300",
+            Color::Green => "bg-emerald-300",
+            Color::Blue => "bg-sky-300",
+            Color::Pink => "bg-rose-300",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Color::Yellow => "yellow",
+            Color::Green => "green",
+            Color::Blue => "blue",
+            Color::Pink => "pink",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Highlight {
+    pub id: u64,
+    pub page: usize,
+    /// `(para, offset)` the highlight starts at, the offset counting
+    /// characters of the paragraph's text.
+    pub start: (usize, usize),
+    /// `(para, offset)` the highlight ends at, exclusive.
+    pub end: (usize, usize),
+    pub color: Color,
+    pub note: String,
+    /// The highlighted passage.
+    pub text: String,
+}
+
+impl Highlight {
+    /// Character range covered within `para`, if any.
+    pub fn range_in(&self, para: usize) -> Option<(usize, usize)> {
+        if para < self.start.0 || para > self.end.0 {
+            return None;
+        }
+        let start = if para == self.start.0 {
+            self.start.1
+        } else {
+            0
+        };
+        let end = if para == self.end.0 {
+            self.end.1
+        } else {
+            usize::MAX
+        };
+        Some((start, end))
+    }
+}
+
+/// Highlights of the open book.
+#[derive(Clone, Copy)]
+pub struct Annotations {
+    pub highlights: RwSignal<Vec<Highlight>>,
+    book: ReadSignal<Book>,
+}
+
+impl Annotations {
+    pub fn add(
+        &self,
+        page: usize,
+        start: (usize, usize),
+        end: (usize, usize),
+        text: String,
+        color: Color,
+    ) -> u64 {
+        let id = self
+            .highlights
+            .with_untracked(|hs| hs.iter().map(|h| h.id).max().map_or(1, |id| id + 1));
+        self.highlights.update(|hs| {
+            hs.push(Highlight {
+                id,
+                page,
+                start,
+                end,
+                color,
+                note: String::new(),
+                text,
+            });
+            hs.sort_by_key(|h| (h.page, h.start));
+        });
+        self.save();
+        id
+    }
+
+    pub fn edit(&self, id: u64, f: impl FnOnce(&mut Highlight)) {
+        self.highlights.update(|hs| {
+            if let Some(h) = hs.iter_mut().find(|h| h.id == id) {
+                f(h);
+            }
+        });
+        self.save();
+    }
+
+    pub fn remove(&self, id: u64) {
+        self.highlights.update(|hs| hs.retain(|h| h.id != id));
+        self.save();
+    }
+
+    pub fn find(&self, id: u64) -> Option<Highlight> {
+        self.highlights
+            .with_untracked(|hs| hs.iter().find(|h| h.id == id).cloned())
+    }
+
+    fn save(&self) {
+        let Some(book) = self.book.get_untracked() else {
+            return;
+        };
+        let key = Key::Highlights(book.identifier());
+        self.highlights.with_untracked(|hs| {
+            if hs.is_empty() {
+                storage::get().remove(key);
+            } else {
+                storage::get().set_json(key, hs);
+            }
+        });
+    }
+}
+
+fn load(id: &str) -> Vec<Highlight> {
+    storage::get()
+        .get_json(Key::Highlights(id))
+        .unwrap_or_default()
+}
+
+pub fn init() {
+    let book = expect_context::<ReadSignal<Book>>();
+    let annotations = Annotations {
+        highlights: create_rw_signal(Vec::new()),
+        book,
+    };
+    provide_context(annotations);
+
+    // hook: when book changes, load its highlights
+    create_effect(move |_| {
+        let highlights = book.with(|book| book.as_ref().map(|b| load(b.identifier())));
+        annotations.highlights.set(highlights.unwrap_or_default());
+    });
+}
+
+pub fn get() -> Annotations {
+    expect_context::<Annotations>()
+}
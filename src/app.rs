@@ -2,15 +2,13 @@ use leptos::*;
 use leptos_router::*;
 
 use crate::{
+    annotations,
     book::{self, Book},
-    components::{Content, NavBar, Settings, Toc, Upload},
-    config, nav_state, position,
+    components::{Bookmarks, Content, Library, NavBar, Search, Settings, Toc, Upload},
+    config, nav_state, overrides, position, search,
 };
 
-// local storage usage (non-normative)
-// "b" => base64 encoded epub (most recently loaded book)
-// "{book identifier}" => "{page}:{para}" (current position)
-// "c" => "{true|false}:{true|false}" (config fields in order)
+// what is kept between sessions is listed in `storage`
 
 fn set_title(title: &str) {
     if !title.is_empty() {
@@ -26,6 +24,9 @@ pub fn App() -> impl IntoView {
     book::init();
     nav_state::init();
     position::init();
+    search::init();
+    annotations::init();
+    overrides::init();
 
     let book = expect_context::<ReadSignal<Book>>();
 
@@ -54,8 +55,11 @@ pub fn App() -> impl IntoView {
                     <Routes base="/wepu".to_string()>
                         <Route path="/" view=main_view>
                             <Route path="" view=Toc />
+                            <Route path="bookmarks" view=Bookmarks />
+                            <Route path="search" view=Search />
                             <Route path=":idx" view=Content />
                         </Route>
+                        <Route path="library" view=Library />
                         <Route path="settings" view=Settings />
                     </Routes>
                 </div>
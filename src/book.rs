@@ -3,71 +3,227 @@ use std::rc::Rc;
 use base64::prelude::*;
 use leptos::*;
 use leptos_router::use_navigate;
-use lepu::Epub;
+use lepu::{Chapter, Epub};
+use web_sys::js_sys::Uint8Array;
 
-use crate::config;
+use crate::{
+    config, library, opf,
+    storage::{self, Key},
+};
 
 pub type Book = Option<Rc<Epub>>;
 
+/// Whether the open book's pages run right to left, as its spine says.
+#[derive(Clone, Copy)]
+pub struct RightToLeft(RwSignal<bool>);
+
+impl RightToLeft {
+    pub fn get(&self) -> bool {
+        self.0.get()
+    }
+}
+
+/// Name of the table of contents entry that `page` falls under.
+pub fn chapter_name(epub: &Epub, page: usize) -> Option<String> {
+    fn walk<'a>(
+        entries: impl Iterator<Item = &'a Chapter>,
+        page: usize,
+        best: &mut Option<(usize, &'a str)>,
+    ) {
+        for entry in entries {
+            let idx = entry.index_in_spine();
+            if idx <= page && best.is_none_or(|(b, _)| idx > b) {
+                *best = Some((idx, entry.name()));
+            }
+            walk(entry.children(), page, best);
+        }
+    }
+
+    let mut best = None;
+    walk(epub.chapters(), page, &mut best);
+    best.map(|(_, name)| name.to_owned())
+}
+
+/// Every table of contents entry as `(name, page)`, in reading order.
+pub fn chapters(epub: &Epub) -> Vec<(String, usize)> {
+    fn walk<'a>(entries: impl Iterator<Item = &'a Chapter>, out: &mut Vec<(String, usize)>) {
+        for entry in entries {
+            out.push((entry.name().to_owned(), entry.index_in_spine()));
+            walk(entry.children(), out);
+        }
+    }
+
+    let mut out = Vec::new();
+    walk(epub.chapters(), &mut out);
+    out
+}
+
+// where books were cached (base64) before the library existed
+const LEGACY_BOOK: Key = Key::Legacy("b");
+
 pub fn init() {
     let (book, set_book) = create_signal::<Option<Rc<Epub>>>(None);
     let (source, set_source) = create_signal(None);
+    let rtl = RightToLeft(create_rw_signal(false));
 
     let config = config::get();
-    let config_ = config.clone();
     let res = create_local_resource(
         move || source.get(),
         move |file: Option<web_sys::File>| async move {
-            use web_sys::js_sys::Uint8Array;
             let file = file?;
             let promise = file.array_buffer();
             let future = wasm_bindgen_futures::JsFuture::from(promise);
             let res = future.await.ok()?;
-            Some(Uint8Array::new(&res).to_vec())
+            let buf = Uint8Array::new(&res).to_vec();
+            // known before the book is shown, so its pages don't flip
+            let right_to_left = opf::right_to_left(&buf).await;
+            Some((buf, right_to_left))
         },
     );
 
     // set book in response to change in resource
     create_effect(move |_| {
-        let Some(Some(buf)) = res.get() else { return };
-        let encoded = BASE64_STANDARD.encode(&buf);
+        let Some(Some((buf, right_to_left))) = res.get() else {
+            return;
+        };
+        // copy the data out first, parsing takes ownership of the buffer
+        let data = Uint8Array::from(&buf[..]);
         let Ok(epub) = Epub::new(buf) else { return };
-        if config_.borrow().cache_book {
-            if let Ok(Some(storage)) = leptos::window().local_storage() {
-                if encoded.len() < 3_000_000 {
-                    let _ = storage.set_item("b", &encoded);
-                } else {
-                    let _ = storage.remove_item("b");
-                }
-            }
-        }
+        // what older versions stored about it, see `Storage::migrate_book`
+        storage::get().migrate_book(epub.identifier());
+        let id = epub.identifier().to_owned();
+        remember(&id);
+        spawn_local(async move {
+            let _ = library::put(&id, &data).await;
+        });
         // reset the resource to save some memory
         set_source.set(None);
+        rtl.0.set(right_to_left);
         set_book.set(Some(Rc::new(epub)));
     });
 
-    // load book from local storage
+    // reopen the most recently opened book from the library
     if config.borrow().cache_book {
-        if let Ok(Some(storage)) = leptos::window().local_storage() {
-            if let Ok(Some(saved_book)) = storage.get_item("b") {
-                if let Ok(data) = BASE64_STANDARD.decode(saved_book) {
-                    let epub = Epub::new(data).ok().unwrap();
-                    set_book.set(Some(Rc::new(epub)));
-                }
-            }
-        }
+        restore(set_book, rtl);
     }
 
+    // keep the shelf entry of the open book up to date
+    create_effect(move |_| {
+        let Some(book) = book.get() else { return };
+        spawn_local(async move {
+            let id = book.identifier();
+            if library::entry(id).await.is_some() {
+                library::touch(id).await;
+            } else {
+                let _ = library::put_entry(&library::Entry::new(&book)).await;
+            }
+        });
+    });
+
     provide_context(set_source);
     provide_context(book);
     provide_context(set_book);
+    provide_context(rtl);
+}
+
+fn restore(set_book: WriteSignal<Book>, rtl: RightToLeft) {
+    let storage = storage::get();
+    if let Some(saved_book) = storage.get(LEGACY_BOOK) {
+        migrate(saved_book, set_book, rtl);
+        return;
+    }
+
+    let Some(id) = storage.get(Key::LastOpened) else {
+        return;
+    };
+    spawn_local(async move {
+        let _ = load(&id, set_book, rtl).await;
+    });
+}
+
+// errs with why the book couldn't be opened
+async fn load(id: &str, set_book: WriteSignal<Book>, rtl: RightToLeft) -> Result<(), &'static str> {
+    let Some(data) = library::get(id).await else {
+        return Err("This book is no longer in the library.");
+    };
+    let right_to_left = opf::right_to_left(&data).await;
+    let Ok(epub) = Epub::new(data) else {
+        return Err("This book could not be read from the library.");
+    };
+    remember(id);
+    storage::get().migrate_book(id);
+    rtl.0.set(right_to_left);
+    set_book.set(Some(Rc::new(epub)));
+    Ok(())
+}
+
+/// Open a book from the library, running `then` once it has loaded, or
+/// telling the reader why it couldn't be.
+pub fn open(id: String, then: impl FnOnce() + 'static) {
+    let set_book = expect_context::<WriteSignal<Book>>();
+    let rtl = expect_context::<RightToLeft>();
+    let owner = Owner::current();
+    spawn_local(async move {
+        if let Err(reason) = load(&id, set_book, rtl).await {
+            let _ = window().alert_with_message(reason);
+            return;
+        }
+        // run within the caller's owner so `then` has access to context
+        match owner {
+            Some(owner) => with_owner(owner, then),
+            None => then(),
+        }
+    });
+}
+
+// move a book cached by an older version into the library
+fn migrate(saved_book: String, set_book: WriteSignal<Book>, rtl: RightToLeft) {
+    let Ok(data) = BASE64_STANDARD.decode(saved_book) else {
+        return;
+    };
+    spawn_local(async move {
+        let right_to_left = opf::right_to_left(&data).await;
+        let array = Uint8Array::from(&data[..]);
+        let Ok(epub) = Epub::new(data) else { return };
+        let id = epub.identifier().to_owned();
+        remember(&id);
+        storage::get().migrate_book(&id);
+        rtl.0.set(right_to_left);
+        set_book.set(Some(Rc::new(epub)));
+        if library::put(&id, &array).await.is_ok() {
+            storage::get().remove(LEGACY_BOOK);
+        }
+    });
+}
+
+fn remember(id: &str) {
+    storage::get().set(Key::LastOpened, id);
+}
+
+// the book reopened on startup
+fn last_opened() -> Option<String> {
+    storage::get().get(Key::LastOpened)
+}
+
+/// Stop reopening the most recently opened book on startup.
+pub fn forget() {
+    storage::get().remove(Key::LastOpened);
 }
 
 pub fn unload() {
     let set_book = expect_context::<WriteSignal<Book>>();
     set_book.set(None);
     (use_navigate())("", Default::default());
-    if let Ok(Some(storage)) = leptos::window().local_storage() {
-        let _ = storage.remove_item("b");
+    forget();
+}
+
+/// Stop reading the book `id` if it is open, and reopening it on startup.
+pub fn close(id: &str) {
+    let book = expect_context::<ReadSignal<Book>>();
+    if book.with_untracked(|b| b.as_ref().is_some_and(|b| b.identifier() == id)) {
+        expect_context::<WriteSignal<Book>>().set(None);
+    }
+    if last_opened().as_deref() == Some(id) {
+        forget();
     }
 }
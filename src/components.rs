@@ -4,11 +4,32 @@ pub use nav_bar::NavBar;
 pub mod settings;
 pub use settings::Settings;
 
+pub mod keymap;
+pub use keymap::Keymap;
+
+pub mod typography;
+pub use typography::Typography;
+
+pub mod library;
+pub use library::Library;
+
 pub mod upload;
 pub use upload::Upload;
 
 pub mod toc;
 pub use toc::Toc;
 
+pub mod bookmarks;
+pub use bookmarks::Bookmarks;
+
+pub mod search;
+pub use search::Search;
+
+pub mod annotator;
+
+pub mod help;
+
+pub mod palette;
+
 pub mod content;
 pub use content::Content;
@@ -0,0 +1,179 @@
+use leptos::*;
+use wasm_bindgen::JsCast as _;
+
+use crate::{
+    annotations::{self, Color},
+    content,
+};
+
+#[derive(Clone, PartialEq)]
+pub enum Editing {
+    /// Text is selected but not highlighted yet.
+    Selection {
+        page: usize,
+        start: (usize, usize),
+        end: (usize, usize),
+        text: String,
+    },
+    Highlight(u64),
+}
+
+// `(page, (para, offset))` of a point within a text node rendered by
+// `chapter_to_html`
+fn locate(node: web_sys::Node, offset: u32) -> Option<(usize, (usize, usize))> {
+    // selecting whole paragraphs, as by triple-clicking, puts the ends of the
+    // selection between elements rather than in text
+    let (node, offset) = match node.dyn_ref::<web_sys::Element>() {
+        Some(element) => text_at(element, offset)?,
+        None => (node, offset),
+    };
+    if node.node_type() != web_sys::Node::TEXT_NODE {
+        return None;
+    }
+    let piece = node.parent_element()?.closest("[data-o]").ok()??;
+    let base = piece.get_attribute("data-o")?.parse::<usize>().ok()?;
+    let (page, para) = content::parse_para_id(&piece.closest("[data-para]").ok()??.id())?;
+    // selection offsets count utf-16 code units
+    let text = node.text_content()?;
+    let chars = char::decode_utf16(text.encode_utf16().take(offset as usize)).count();
+    Some((page, (para, base + chars)))
+}
+
+// the point in text where child `offset` of `element` begins: the start of the
+// first piece of text from there on, or the end of the last one if none follows
+fn text_at(element: &web_sys::Element, offset: u32) -> Option<(web_sys::Node, u32)> {
+    let pieces = element.query_selector_all("[data-o]").ok()?;
+    let pieces = (0..pieces.length())
+        .filter_map(|i| pieces.item(i))
+        .collect::<Vec<_>>();
+    let following = match element.child_nodes().item(offset) {
+        Some(child) => pieces.iter().find(|&piece| {
+            child.contains(Some(piece))
+                || child.compare_document_position(piece)
+                    & web_sys::Node::DOCUMENT_POSITION_FOLLOWING
+                    != 0
+        }),
+        None => None,
+    };
+    match following {
+        Some(piece) => Some((piece.first_child()?, 0)),
+        None => {
+            let text = pieces.last()?.first_child()?;
+            let len = text.text_content()?.encode_utf16().count();
+            Some((text, len as u32))
+        }
+    }
+}
+
+/// The current text selection, if any and within a single page.
+pub fn selected() -> Option<Editing> {
+    let selection = window().get_selection().ok()??;
+    if selection.is_collapsed() {
+        return None;
+    }
+    let (page, anchor) = locate(selection.anchor_node()?, selection.anchor_offset())?;
+    let (focus_page, focus) = locate(selection.focus_node()?, selection.focus_offset())?;
+    if page != focus_page {
+        return None;
+    }
+    let (start, end) = if anchor <= focus {
+        (anchor, focus)
+    } else {
+        (focus, anchor)
+    };
+    Some(Editing::Selection {
+        page,
+        start,
+        end,
+        text: selection.to_string().into(),
+    })
+}
+
+fn clear_selection() {
+    if let Ok(Some(selection)) = window().get_selection() {
+        let _ = selection.remove_all_ranges();
+    }
+}
+
+#[component]
+pub fn Annotator(editing: RwSignal<Option<Editing>>) -> impl IntoView {
+    let annotations = annotations::get();
+
+    let highlight = move || match editing.get() {
+        Some(Editing::Highlight(id)) => annotations
+            .highlights
+            .with(|hs| hs.iter().find(|h| h.id == id).cloned()),
+        _ => None,
+    };
+
+    let pick = move |color: Color| match editing.get_untracked() {
+        Some(Editing::Selection {
+            page,
+            start,
+            end,
+            text,
+        }) => {
+            let id = annotations.add(page, start, end, text, color);
+            clear_selection();
+            editing.set(Some(Editing::Highlight(id)));
+        }
+        Some(Editing::Highlight(id)) => annotations.edit(id, |h| h.color = color),
+        None => {}
+    };
+
+    let set_note = move |ev| {
+        if let Some(Editing::Highlight(id)) = editing.get_untracked() {
+            let note = event_target_value(&ev);
+            annotations.edit(id, |h| h.note = note);
+        }
+    };
+
+    let remove = move |_| {
+        if let Some(Editing::Highlight(id)) = editing.get_untracked() {
+            annotations.remove(id);
+        }
+        editing.set(None);
+    };
+
+    let close = move |_| {
+        clear_selection();
+        editing.set(None);
+    };
+
+    let swatches = move || {
+        Color::ALL
+            .into_iter()
+            .map(|color| {
+                let class = format!("w-6 h-6 rounded-full {}", color.swatch());
+                view! {
+                    <button class=class title=color.name() on:click=move |_| pick(color)></button>
+                }
+            })
+            .collect_view()
+    };
+
+    view! {
+        <Show when=move || editing.with(Option::is_some)>
+            <div class="fixed inset-x-0 bottom-0 z-10 flex justify-center">
+                <div class="w-full max-w-screen-sm md:max-w-screen-md mx-2 mb-2 p-3 space-y-3
+                            rounded-lg border border-zinc-600 font-sans text-base
+                            bg-page">
+                    <div class="flex items-center space-x-3">
+                        {swatches}
+                        <div class="flex-1"></div>
+                        <Show when=move || highlight().is_some()>
+                            <button class="hover:text-link" on:click=remove>"delete"</button>
+                        </Show>
+                        <button class="hover:text-link" on:click=close>"close"</button>
+                    </div>
+                    <Show when=move || highlight().is_some()>
+                        <textarea class="w-full rounded-lg bg-transparent"
+                                  placeholder="note"
+                                  prop:value=move || highlight().map(|h| h.note).unwrap_or_default()
+                                  on:change=set_note />
+                    </Show>
+                </div>
+            </div>
+        </Show>
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+
+use leptos::*;
+use wasm_bindgen::JsValue;
+use web_sys::js_sys::Date;
+
+use crate::{
+    book::{self, Book},
+    content, library,
+    nav_state::{set_nav_state, NavState},
+    position::{self, Bookmark, GlobalMarks, LastVisible, Mark, Marks},
+};
+
+fn format_time(time: f64) -> String {
+    if time <= 0.0 {
+        return String::new();
+    }
+    Date::new(&JsValue::from_f64(time))
+        .to_locale_string("default", &JsValue::UNDEFINED)
+        .into()
+}
+
+enum Entry {
+    Mark(char, Mark),
+    Global(char, position::GlobalMark),
+    Bookmark(usize, Bookmark),
+}
+
+impl Entry {
+    fn label(&self) -> String {
+        match self {
+            Entry::Mark(name, _) | Entry::Global(name, _) => format!("'{name}"),
+            Entry::Bookmark(_, bookmark) => bookmark.name.clone(),
+        }
+    }
+
+    fn position(&self) -> (usize, usize, f64) {
+        match self {
+            Entry::Mark(_, m) => (m.page, m.para, m.time),
+            Entry::Global(_, m) => (m.page, m.para, m.time),
+            Entry::Bookmark(_, b) => (b.page, b.para, b.time),
+        }
+    }
+}
+
+#[component]
+pub fn Bookmarks() -> impl IntoView {
+    set_nav_state(NavState::Bookmarks);
+
+    let book = expect_context::<ReadSignal<Book>>();
+    let page = expect_context::<ReadSignal<usize>>();
+    let last_visible = expect_context::<LastVisible>();
+    let marks = store_value(expect_context::<Marks>());
+    let global_marks = store_value(expect_context::<GlobalMarks>());
+    let bookmarks = store_value(expect_context::<position::Bookmarks>());
+    // marks aren't reactive, so changes made here are signalled manually
+    let changed = create_trigger();
+
+    // titles of other books global marks may point into
+    let titles = create_local_resource(
+        || (),
+        |_| async {
+            library::entries()
+                .await
+                .into_iter()
+                .map(|e| (e.id, e.title))
+                .collect::<BTreeMap<_, _>>()
+        },
+    );
+
+    let save = move || {
+        let Some(book) = book.get_untracked() else {
+            return;
+        };
+        let id = book.identifier();
+        marks.with_value(|m| position::save_marks(id, &m.borrow()));
+        bookmarks.with_value(|b| position::save_bookmarks(id, &b.borrow()));
+        global_marks.with_value(|g| position::save_global_marks(&g.borrow()));
+        changed.notify();
+    };
+
+    let remove = move |entry: &Entry| {
+        match *entry {
+            Entry::Mark(name, _) => marks.with_value(|m| _ = m.borrow_mut().remove(&name)),
+            Entry::Global(name, _) => global_marks.with_value(|g| _ = g.borrow_mut().remove(&name)),
+            Entry::Bookmark(idx, _) => bookmarks.with_value(|b| _ = b.borrow_mut().remove(idx)),
+        }
+        save();
+    };
+
+    // errs rather than replace a mark already set under the new name
+    let rename = move |entry: &Entry, to: String| -> Result<(), String> {
+        match *entry {
+            Entry::Mark(name, _) => {
+                let Some(to) = single_char(&to).filter(char::is_ascii_lowercase) else {
+                    return Ok(());
+                };
+                marks.with_value(|m| {
+                    let mut m = m.borrow_mut();
+                    if to != name && m.contains_key(&to) {
+                        return Err(format!("'{to} is already set"));
+                    }
+                    if let Some(mark) = m.remove(&name) {
+                        m.insert(to, mark);
+                    }
+                    Ok(())
+                })?;
+            }
+            Entry::Global(name, _) => {
+                let Some(to) = single_char(&to).filter(char::is_ascii_uppercase) else {
+                    return Ok(());
+                };
+                global_marks.with_value(|g| {
+                    let mut g = g.borrow_mut();
+                    if to != name && g.contains_key(&to) {
+                        return Err(format!("'{to} is already set"));
+                    }
+                    if let Some(mark) = g.remove(&name) {
+                        g.insert(to, mark);
+                    }
+                    Ok(())
+                })?;
+            }
+            Entry::Bookmark(idx, _) => {
+                if to.trim().is_empty() {
+                    return Ok(());
+                }
+                bookmarks.with_value(|b| {
+                    if let Some(bookmark) = b.borrow_mut().get_mut(idx) {
+                        bookmark.name = to.trim().to_owned();
+                    }
+                });
+            }
+        }
+        save();
+        Ok(())
+    };
+
+    let jump = move |entry: &Entry| match entry {
+        Entry::Global(_, mark) => position::follow_global(mark.clone()),
+        _ => {
+            let (page, para, _) = entry.position();
+            position::jump_to(page, para);
+        }
+    };
+
+    let input_element: NodeRef<html::Input> = create_node_ref();
+    let add = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        let input = input_element.get().unwrap();
+        let name = input.value().trim().to_owned();
+        if name.is_empty() {
+            return;
+        }
+        let (page, para) = last_visible.get().unwrap_or((page.get_untracked(), 0));
+        bookmarks.with_value(|b| {
+            b.borrow_mut().push(Bookmark {
+                name,
+                page,
+                para,
+                time: Date::now(),
+            })
+        });
+        input.set_value("");
+        save();
+    };
+
+    let row = move |entry: Entry| {
+        let book = book.get()?;
+        let (page, para, time) = entry.position();
+        let (chapter, excerpt) = match &entry {
+            Entry::Global(_, mark) if mark.book != book.identifier() => {
+                let title = titles
+                    .get()
+                    .and_then(|t| t.get(&mark.book).cloned())
+                    .unwrap_or_else(|| "another book".to_owned());
+                (title, String::new())
+            }
+            _ => (
+                book::chapter_name(&book, page).unwrap_or_default(),
+                content::excerpt(&book, page, para).unwrap_or_default(),
+            ),
+        };
+        let renaming = create_rw_signal(false);
+        let error = create_rw_signal(None::<String>);
+        let label = entry.label();
+        let entry = store_value(entry);
+        Some(view! {
+            <li class="pb-5">
+                <div class="flex items-baseline space-x-3">
+                    <Show when=move || renaming.get()
+                          fallback=move || view! { <span class="font-bold">{label.clone()}</span> }>
+                        <input class="rounded-lg bg-transparent py-0"
+                               on:change=move |ev| {
+                                   match entry.with_value(|e| rename(e, event_target_value(&ev))) {
+                                       Ok(()) => renaming.set(false),
+                                       Err(e) => error.set(Some(e)),
+                                   }
+                               } />
+                        <span class="text-sm text-rose-500">{move || error.get()}</span>
+                    </Show>
+                    <span class="text-sm">{chapter}</span>
+                    <span class="flex-1 text-right text-sm">{format_time(time)}</span>
+                </div>
+                <p class="font-serif">{excerpt}</p>
+                <div class="text-sm space-x-3">
+                    <button class="hover:text-link" on:click=move |_| entry.with_value(jump)>"jump"</button>
+                    <button class="hover:text-link" on:click=move |_| {
+                        error.set(None);
+                        renaming.set(true);
+                    }>"rename"</button>
+                    <button class="hover:text-link" on:click=move |_| entry.with_value(remove)>"delete"</button>
+                </div>
+            </li>
+        })
+    };
+
+    let list = move || {
+        changed.track();
+        let mut entries = Vec::new();
+        marks.with_value(|m| {
+            entries.extend(
+                m.borrow()
+                    .iter()
+                    .map(|(&name, &mark)| Entry::Mark(name, mark)),
+            )
+        });
+        global_marks.with_value(|g| {
+            entries.extend(
+                g.borrow()
+                    .iter()
+                    .map(|(&name, mark)| Entry::Global(name, mark.clone())),
+            )
+        });
+        bookmarks.with_value(|b| {
+            entries.extend(
+                b.borrow()
+                    .iter()
+                    .cloned()
+                    .enumerate()
+                    .map(|(idx, bookmark)| Entry::Bookmark(idx, bookmark)),
+            )
+        });
+        if entries.is_empty() {
+            return view! { <p>"No marks or bookmarks yet."</p> }.into_view();
+        }
+        view! { <ul>{entries.into_iter().map(row).collect_view()}</ul> }.into_view()
+    };
+
+    view! {
+        <h1 class="mt-8 mb-10 text-left font-sans font-bold text-2xl md:text-4xl tracking-tight leading-none">
+            Bookmarks
+        </h1>
+
+        <form class="flex mb-8 space-x-3 text-base" on:submit=add>
+            <input class="flex-1 rounded-lg bg-transparent" placeholder="bookmark name" node_ref=input_element />
+            <button class="hover:text-link" type="submit">"add at current position"</button>
+        </form>
+
+        <div class="text-base">{list}</div>
+    }
+}
+
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.trim().chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
+}
@@ -4,12 +4,21 @@ use leptos::*;
 use leptos_router::*;
 
 use crate::{
-    book::Book,
-    content::chapter_to_html,
-    input::{Action, Handler},
-    nav_state::NavState,
-    position::Tracker,
-    set_nav_state,
+    annotations,
+    book::{Book, RightToLeft},
+    components::{
+        annotator::{selected, Annotator, Editing},
+        help::Help,
+        palette::Palette,
+    },
+    config::{self, Layout},
+    content::{self, chapter_to_html},
+    export,
+    input::{key_name, Action, Handler, Modifiers},
+    nav_state::{self, NavState},
+    overrides, pages,
+    position::{self, GlobalMarks, Tracker},
+    search, set_nav_state, Marks,
 };
 
 #[derive(Params, Debug, Clone, PartialEq)]
@@ -38,55 +47,373 @@ pub fn Content() -> impl IntoView {
 
     let tracker_ = Rc::new(Tracker::init());
     let tracker = tracker_.clone();
-    let handler = RefCell::new(Handler::new());
+    let search = search::get();
+    let annotations = annotations::get();
+    let marks = expect_context::<Marks>();
+    let global_marks = expect_context::<GlobalMarks>();
+    let config = config::get();
+    let overrides = overrides::get();
+    let handler = RefCell::new(Handler::new(&config.borrow().keymap));
+    let (pending, set_pending) = create_signal(None::<String>);
+    let help = create_rw_signal(false);
+    let palette = create_rw_signal(false);
+    let (keymap, set_keymap) = create_signal(handler.borrow().keymap().clone());
 
-    let handle = window_event_listener(ev::keyup, move |ev: ev::KeyboardEvent| {
-        if ev.alt_key() || ev.shift_key() || ev.meta_key() || ev.ctrl_key() {
+    // runs actions from keys as well as from the command line
+    let dispatch: Rc<dyn Fn(Action)> = Rc::new(move |action| match action {
+        Action::NextPage(count) => tracker.next_page(count),
+        Action::PreviousPage(count) => tracker.previous_page(count),
+        Action::GoToPage(page) => tracker.go_to_page(page),
+        Action::GoToPercent(fraction) => tracker.go_to_percent(fraction),
+        Action::Top => tracker.scroll_to_top(),
+        Action::Bottom => tracker.scroll_to_bottom(),
+        Action::NextParagraph(count) => tracker.next_paragraph(count),
+        Action::PreviousParagraph(count) => tracker.previous_paragraph(count),
+        Action::HalfPageDown(count) => tracker.scroll_screens(count as f64 / 2.0),
+        Action::HalfPageUp(count) => tracker.scroll_screens(count as f64 / -2.0),
+        Action::SetMark(c) => tracker.set_mark(c),
+        Action::FollowMark(c) => tracker.follow_mark(c),
+        Action::Search { query, backwards } => {
+            let Some(book) = book.get_untracked() else {
+                return;
+            };
+            search.run(&book, query);
+            search.backwards.set(backwards);
+            search.step(tracker.position(), false);
+        }
+        Action::NextMatch => search.step(tracker.position(), false),
+        Action::PreviousMatch => search.step(tracker.position(), true),
+        Action::JumpBack => position::jump_back(),
+        Action::JumpForward => position::jump_forward(),
+        Action::Help => help.set(true),
+        Action::CommandLine => palette.set(true),
+        Action::SetTheme(theme) => {
+            overrides.update("theme", |c| c.theme = theme);
+        }
+        Action::Export(format) => {
+            let Some(book) = book.get_untracked() else {
+                return;
+            };
+            annotations.highlights.with_untracked(|highlights| {
+                let marks = position::book_marks(book.identifier(), &marks, &global_marks);
+                export::notes(&book, highlights, &marks, format);
+            });
+        }
+        Action::ToggleNavBar => nav_state::toggle_nav_bar(),
+        Action::Leave => (use_navigate())("/", Default::default()),
+    });
+
+    // keydown rather than keyup so typed search queries arrive in order
+    let dispatch_ = dispatch.clone();
+    let handle = window_event_listener(ev::keydown, move |ev: ev::KeyboardEvent| {
+        // modifiers only count along with another key
+        let key = ev.key();
+        if ev.repeat() || matches!(key.as_str(), "Control" | "Alt" | "Meta" | "Shift") {
+            return;
+        }
+        // typing into a note or other field
+        let tag = event_target::<web_sys::Element>(&ev).tag_name();
+        if tag == "INPUT" || tag == "TEXTAREA" {
             return;
         }
-        let Some(action) = handler.borrow_mut().handle(&*ev.key()) else {
+        if help.get_untracked() {
+            ev.prevent_default();
+            help.set(false);
+            return;
+        }
+        let mut handler = handler.borrow_mut();
+        // keys may have been rebound since the reader was opened
+        let config = config.borrow();
+        if handler.keymap() != &config.keymap {
+            *handler = Handler::new(&config.keymap);
+            set_keymap.set(config.keymap.clone());
+        }
+        drop(config);
+        let was_pending = handler.pending().is_some();
+        let modifiers = Modifiers {
+            ctrl: ev.ctrl_key(),
+            alt: ev.alt_key(),
+            meta: ev.meta_key(),
+        };
+        let action = handler.handle(&key_name(&key, modifiers));
+        let now_pending = handler.pending();
+        if was_pending || now_pending.is_some() || action.is_some() {
+            // keep the browser from acting on keys like `/` (quick find) or
+            // ctrl-o (open file)
+            ev.prevent_default();
+        }
+        set_pending.set(now_pending);
+        if let Some(action) = action {
+            dispatch_(action);
+        }
+    });
+
+    // wide enough for two screens side by side
+    let is_wide = || {
+        window()
+            .inner_width()
+            .ok()
+            .and_then(|w| w.as_f64())
+            .unwrap_or(0.0)
+            >= 1024.0
+    };
+    let (wide, set_wide) = create_signal(is_wide());
+
+    // screens change size with the window, keep the same text in view
+    let tracker = tracker_.clone();
+    let resize = window_event_listener(ev::resize, move |_| {
+        set_wide.set(is_wide());
+        tracker.reveal_first_visible();
+    });
+
+    // the book may override how it's rendered, and another book may open
+    // while this stays mounted
+    let settings = overrides.settings();
+    let layout = create_memo(move |_| settings.with(|s| s.layout));
+    let paginated = move || layout.get() == Layout::Paginated;
+    let continuous = move || layout.get() == Layout::Continuous;
+
+    // pages rendered when continuous, in order
+    let shown = create_rw_signal(vec![page.get_untracked()]);
+    // render the pages around the ones shown as the reader nears them
+    let extend = move || {
+        let Some(count) = book.with_untracked(|b| b.as_ref().map(|b| b.document_count())) else {
+            return;
+        };
+        let Some((first, last)) = shown.with_untracked(|s| Some((*s.first()?, *s.last()?))) else {
             return;
         };
+        let window = window();
+        let height = window
+            .inner_height()
+            .ok()
+            .and_then(|h| h.as_f64())
+            .unwrap_or(0.0);
+        let y = window.scroll_y().unwrap_or(0.0);
+        let total = document().body().map_or(0, |body| body.scroll_height()) as f64;
+        if total - (y + height) < height && last + 1 < count {
+            shown.update(|s| s.push(last + 1));
+        } else if y < height && first > 0 {
+            // keep the text on screen in place as the page above appears
+            let anchor = content::page_element(first);
+            let before = anchor.as_ref().map(|a| a.get_bounding_client_rect().top());
+            shown.update(|s| s.insert(0, first - 1));
+            if let (Some(anchor), Some(before)) = (anchor, before) {
+                window
+                    .scroll_by_with_x_and_y(0.0, anchor.get_bounding_client_rect().top() - before);
+            }
+        }
+    };
+    let scroll = window_event_listener(ev::scroll, move |_| {
+        if layout.get_untracked() == Layout::Continuous {
+            extend();
+        }
+    });
 
-        match action {
-            Action::NextPage => tracker.move_to_next_page(),
-            Action::PreviousPage => tracker.move_to_previous_page(),
-            Action::SetMark(c) => tracker.set_mark(c),
-            Action::FollowMark(c) => tracker.follow_mark(c),
-            Action::Leave => (use_navigate())("/", Default::default()),
+    // the page the url was changed to by scrolling, as opposed to one
+    // navigated to, which is rendered on its own again
+    let scrolled_to = store_value(None::<usize>);
+    create_effect(move |_| {
+        let page = page.get();
+        if continuous() && scrolled_to.get_value() != Some(page) {
+            shown.set(vec![page]);
+        }
+    });
+
+    let tracker = tracker_.clone();
+    let navigate = use_navigate();
+    create_effect(move |_| {
+        if !continuous() {
+            return;
+        }
+        let Some((first, _)) = tracker.first_visible() else {
+            return;
+        };
+        if first != page.get_untracked() {
+            scrolled_to.set_value(Some(first));
+            let options = NavigateOptions {
+                replace: true,
+                scroll: false,
+                ..Default::default()
+            };
+            navigate(&first.to_string(), options);
         }
     });
 
     on_cleanup(move || {
         handle.remove();
+        resize.remove();
+        scroll.remove();
     });
 
     let tracker = tracker_.clone();
-    let move_next = move || tracker.move_to_next_page();
+    let move_next = move || tracker.next_page(1);
     let tracker = tracker_.clone();
-    let move_previous = move || tracker.move_to_previous_page();
+    let move_previous = move || tracker.previous_page(1);
+
+    let editing = create_rw_signal(None::<Editing>);
+    // where and when a touch started, to tell taps from swipes
+    let touch_start = store_value(None::<(i32, i32, f64)>);
+    let on_pointerdown = move |ev: ev::PointerEvent| {
+        let start =
+            (ev.pointer_type() == "touch").then(|| (ev.client_x(), ev.client_y(), ev.time_stamp()));
+        touch_start.set_value(start);
+    };
+    let config_ = config.clone();
+    let dispatch_ = dispatch.clone();
+    let on_pointerup = move |ev: ev::PointerEvent| {
+        if let Some(selection) = selected() {
+            editing.set(Some(selection));
+            return;
+        }
+        let Some((x, y, time)) = touch_start.get_value() else {
+            return;
+        };
+        touch_start.set_value(None);
+        let (dx, dy) = (ev.client_x() - x, ev.client_y() - y);
+        let config = config_.borrow();
+
+        let action = if dx.abs() > 60 && dx.abs() > 2 * dy.abs() {
+            if !config.swipe {
+                return;
+            }
+            // content follows the finger, like turning a page
+            if dx < 0 {
+                Action::NextPage(1)
+            } else {
+                Action::PreviousPage(1)
+            }
+        } else if dx.abs() < 10 && dy.abs() < 10 && ev.time_stamp() - time < 300.0 {
+            // taps on highlights and links do their own thing
+            let target = event_target::<web_sys::Element>(&ev);
+            if let Ok(Some(_)) = target.closest("mark[data-h], a, button") {
+                return;
+            }
+            let width = window()
+                .inner_width()
+                .ok()
+                .and_then(|w| w.as_f64())
+                .unwrap_or(1.0);
+            let zone = ((ev.client_x() as f64 / width * 3.0) as usize).min(2);
+            let Some(action) = config.tap[zone].action() else {
+                return;
+            };
+            action
+        } else {
+            return;
+        };
+        drop(config);
+        dispatch_(action);
+    };
+    let on_click = move |ev: ev::MouseEvent| {
+        if selected().is_some() {
+            return;
+        }
+        let mark = event_target::<web_sys::Element>(&ev)
+            .closest("mark[data-h]")
+            .ok()
+            .flatten();
+        let id = mark.and_then(|mark| mark.get_attribute("data-h")?.parse().ok());
+        if let Some(id) = id {
+            editing.set(Some(Editing::Highlight(id)));
+        }
+    };
+
+    let spread = move || paginated() && settings.with(|s| s.spread);
+    // a spread's pages run the way the book's spine says, right to left in
+    // manga or arabic, whatever the direction of the text on them
+    let right_to_left = expect_context::<RightToLeft>();
+    let class = move || match layout.get() {
+        Layout::Scroll => "mt-8 touch-pan-y",
+        Layout::Paginated => "mt-8 touch-pan-y overflow-hidden",
+        Layout::Continuous => "mt-8 touch-pan-y space-y-16",
+    };
+    let typography = create_memo(move |_| settings.with(|s| s.typography.style()));
+    let style = move || {
+        let layout = if continuous() {
+            // `extend` keeps the text in place itself
+            "overflow-anchor: none;".to_owned()
+        } else if paginated() {
+            let columns = format!(
+                "height: calc(100vh - 10rem); column-gap: {}px; column-fill: auto;",
+                pages::GAP
+            );
+            match spread() && wide.get() {
+                // wider than the page around it, centered on it
+                true => format!(
+                    "{columns} column-count: 2; width: min(calc(100vw - 4rem), 96rem); max-width: none; \
+                     position: relative; left: 50%; transform: translateX(-50%);"
+                ),
+                false => format!("{columns} column-count: 1;"),
+            }
+        } else {
+            String::new()
+        };
+        format!("{} {layout}", typography.get())
+    };
 
     let tracker = tracker_.clone();
     view! {
         {move || {
             let tracker = tracker.clone();
+            let on_pointerup = on_pointerup.clone();
+            let style = style.clone();
+            // continuously, pages are added to rather than rendered anew
+            let continuous = continuous();
+            let current = if continuous { page.get_untracked() } else { page.get() };
+            let rtl = spread() && right_to_left.get();
+            let chapter = match continuous {
+                true => view! {
+                    <For each=move || shown.get()
+                         key=|page| *page
+                         children=move |page| {
+                             let tracker = tracker.clone();
+                             request_animation_frame(extend);
+                             move || chapter_to_html(tracker.clone(), book, page)
+                         } />
+                }
+                .into_view(),
+                false => {
+                    let chapter = chapter_to_html(tracker.clone(), book, current);
+                    request_animation_frame(move || tracker.rendered());
+                    chapter.into_view()
+                }
+            };
+            // only the order of the pages is reversed, text keeps its own
+            // direction
+            let chapter = match rtl {
+                true => view! { <div dir="auto">{chapter}</div> }.into_view(),
+                false => chapter,
+            };
             view! {
-                <div class="sm:text-justify font-serif font-light space-y-3 md:space-y-5 mt-8">
-                    {chapter_to_html(tracker, book, page)}
+                <div class=class
+                     style=style
+                     dir=rtl.then_some("rtl")
+                     data-paginated=paginated().then_some("")
+                     on:pointerdown=on_pointerdown
+                     on:pointerup=on_pointerup
+                     on:click=on_click>
+                    {chapter}
                 </div>
             }
         }}
         <div class="flex justify-center pt-2 md:pt-4 pb-4">
             <div>
-                <button class="mt-2 px-3 hover:text-sky-500"
+                <button class="mt-2 px-3 hover:text-link"
                         on:click=move |_| move_previous()>
                     "←"
                 </button>
-                <button class="mt-2 px-3 hover:text-sky-500"
+                <button class="mt-2 px-3 hover:text-link"
                         on:click=move |_| move_next()>
                     "→"
                 </button>
             </div>
         </div>
+        <div class="fixed bottom-2 left-2 font-sans text-sm">
+            {move || pending.get()}
+        </div>
+        <Annotator editing=editing />
+        <Help keymap=keymap open=help />
+        <Palette open=palette dispatch=dispatch />
     }
 }
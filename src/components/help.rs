@@ -0,0 +1,41 @@
+use leptos::*;
+
+use crate::keymap::{Command, Keymap};
+
+/// Overlay listing every command and the keys it's bound to.
+#[component]
+pub fn Help(keymap: ReadSignal<Keymap>, open: RwSignal<bool>) -> impl IntoView {
+    let rows = move || {
+        keymap.with(|keymap| {
+            Command::ALL
+                .into_iter()
+                .map(|command| {
+                    let keys = keymap.sequences(command).collect::<Vec<_>>().join(" ");
+                    view! {
+                        <tr>
+                            <td class="pr-4 font-mono whitespace-nowrap">{keys}</td>
+                            <td>{command.description()}</td>
+                        </tr>
+                    }
+                })
+                .collect_view()
+        })
+    };
+
+    view! {
+        <Show when=move || open.get()>
+            <div class="fixed inset-0 z-20 flex items-center justify-center bg-black/50"
+                 on:click=move |_| open.set(false)>
+                <div class="w-full max-w-screen-sm md:max-w-screen-md max-h-full overflow-y-auto mx-2 p-4
+                            rounded-lg border border-zinc-600 font-sans text-base
+                            bg-page">
+                    <h2 class="mb-4 font-bold text-xl">Keys</h2>
+                    <table>{rows}</table>
+                    <p class="mt-4 text-sm">
+                        "A number before a command repeats it, or picks a chapter. Press any key to close."
+                    </p>
+                </div>
+            </div>
+        </Show>
+    }
+}
@@ -0,0 +1,78 @@
+use leptos::*;
+
+use crate::{
+    config,
+    keymap::{self, Command},
+};
+
+/// Editor for the key sequences bound to each command.
+#[component]
+pub fn Keymap() -> impl IntoView {
+    let config = store_value(config::get());
+    // bumped on reset, so the inputs show the default bindings again
+    let (generation, set_generation) = create_signal(0);
+
+    let row = move |command: Command| {
+        let (error, set_error) = create_signal(None::<String>);
+        let bound = move || {
+            generation.track();
+            config.with_value(|c| {
+                c.borrow()
+                    .keymap
+                    .sequences(command)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+        };
+        let rebind = move |ev| {
+            let value = event_target_value(&ev);
+            let sequences: Vec<String> = value.split_whitespace().map(str::to_owned).collect();
+            if let Some(invalid) = sequences
+                .iter()
+                .find(|s| keymap::parse_sequence(s).is_none())
+            {
+                set_error.set(Some(format!("invalid key sequence \"{invalid}\"")));
+                return;
+            }
+            set_error.set(None);
+            config.with_value(|c| {
+                c.borrow_mut().keymap.rebind(command, sequences);
+                c.borrow().save();
+            });
+        };
+        view! {
+            <li class="flex flex-col">
+                <label class="flex items-baseline space-x-3">
+                    <span class="flex-1">{command.description()}</span>
+                    <input class="w-40 rounded-lg bg-transparent py-0 font-mono"
+                           prop:value=bound on:change=rebind />
+                </label>
+                <span class="text-sm text-rose-500">{error}</span>
+            </li>
+        }
+    };
+
+    let reset = move |_| {
+        config.with_value(|c| {
+            c.borrow_mut().keymap = keymap::Keymap::default();
+            c.borrow().save();
+        });
+        set_generation.update(|g| *g += 1);
+    };
+
+    view! {
+        <h2 class="mt-8 mb-4 font-sans font-bold text-xl">Keys</h2>
+        <p class="mb-4 text-sm">
+            "Sequences are separated by spaces, special keys are written like "
+            <code>"<C-o>"</code>", "<code>"<Right>"</code>" or "<code>"<Esc>"</code>
+            ", with "<code>"C-"</code>", "<code>"A-"</code>" and "<code>"M-"</code>
+            " for ctrl, alt and meta."
+        </p>
+        <ul class="space-y-2">
+            {Command::ALL.into_iter().map(row).collect_view()}
+        </ul>
+        <div>
+            <button class="mt-4 hover:text-link" on:click=reset>"Reset keys to defaults"</button>
+        </div>
+    }
+}
@@ -0,0 +1,128 @@
+use leptos::*;
+use leptos_router::use_navigate;
+
+use crate::{
+    book,
+    library::{self, Entry},
+    nav_state::{set_nav_state, NavState},
+    position,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Order {
+    Opened,
+    Added,
+    Title,
+    Author,
+}
+
+impl Order {
+    fn parse(s: &str) -> Self {
+        match s {
+            "added" => Order::Added,
+            "title" => Order::Title,
+            "author" => Order::Author,
+            _ => Order::Opened,
+        }
+    }
+
+    fn sort(self, entries: &mut [Entry]) {
+        match self {
+            Order::Opened => entries.sort_by(|a, b| b.opened.total_cmp(&a.opened)),
+            Order::Added => entries.sort_by(|a, b| b.added.total_cmp(&a.added)),
+            Order::Title => entries.sort_by_cached_key(|e| e.title.to_lowercase()),
+            Order::Author => entries.sort_by_cached_key(|e| e.author.to_lowercase()),
+        }
+    }
+}
+
+fn progress(entry: &Entry) -> usize {
+    let Some((page, _)) = position::saved(&entry.id) else {
+        return 0;
+    };
+    (page + 1) * 100 / entry.document_count.max(1)
+}
+
+#[component]
+pub fn Library() -> impl IntoView {
+    set_nav_state(NavState::Library);
+
+    let (order, set_order) = create_signal(Order::Opened);
+    let entries = create_local_resource(|| (), |_| library::entries());
+
+    let open = |id: String| book::open(id, || (use_navigate())("/", Default::default()));
+    // what was stored about the book stays, for if it's added again
+    let remove = move |id: String, title: &str| {
+        let message = format!("Remove “{title}” from the library?");
+        if !window().confirm_with_message(&message).unwrap_or(false) {
+            return;
+        }
+        book::close(&id);
+        spawn_local(async move {
+            let _ = library::remove(&id).await;
+            entries.refetch();
+        });
+    };
+
+    let shelf = move || {
+        let mut entries = entries.get()?;
+        order.get().sort(&mut entries);
+        if entries.is_empty() {
+            return Some(view! { <p>"No books yet."</p> }.into_view());
+        }
+        let list = entries
+            .into_iter()
+            .map(|entry| {
+                let progress = progress(&entry);
+                let open_id = entry.id.clone();
+                let remove_id = entry.id.clone();
+                let remove_title = entry.title.clone();
+                view! {
+                    <li class="flex space-x-4">
+                        <div class="w-16 md:w-20 shrink-0">
+                            {entry.cover.map(|src| view! { <img src=src /> })}
+                        </div>
+                        <div class="flex flex-col">
+                            <button class="text-left font-bold hover:text-link"
+                                    on:click=move |_| open(open_id.clone())>
+                                {entry.title}
+                            </button>
+                            <span class="font-serif">{entry.author}</span>
+                            <span class="text-sm">{format!("{progress}% read")}</span>
+                            <div>
+                                <button class="text-sm hover:text-link"
+                                        on:click=move |_| remove(remove_id.clone(), &remove_title)>
+                                    "remove"
+                                </button>
+                            </div>
+                        </div>
+                    </li>
+                }
+            })
+            .collect_view();
+        Some(view! { <ul class="space-y-6">{list}</ul> }.into_view())
+    };
+
+    view! {
+        <h1 class="mt-8 mb-10 text-left font-sans font-bold text-2xl md:text-4xl tracking-tight leading-none">
+            Library
+        </h1>
+
+        <div class="mb-6 text-base">
+            <label>
+                <span class="mr-2">sort by</span>
+                <select class="rounded-xs bg-transparent text-base py-0"
+                        on:change=move |ev| set_order.set(Order::parse(&event_target_value(&ev)))>
+                    <option value="opened">last opened</option>
+                    <option value="added">date added</option>
+                    <option value="title">title</option>
+                    <option value="author">author</option>
+                </select>
+            </label>
+        </div>
+
+        <div class="text-base">
+            <Suspense>{shelf}</Suspense>
+        </div>
+    }
+}
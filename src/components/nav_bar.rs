@@ -3,7 +3,7 @@ use leptos_router::A;
 
 use crate::{
     book::{self, Book},
-    nav_state::NavState,
+    nav_state::{self, NavState},
 };
 
 #[component]
@@ -11,22 +11,26 @@ pub fn NavBar(book: ReadSignal<Book>) -> impl IntoView {
     let book_exists = move || matches!(book.get(), Some(_));
     let page = expect_context::<ReadSignal<usize>>();
     let nav_state = expect_context::<ReadSignal<NavState>>();
+    let hidden = nav_state::nav_bar_hidden;
 
     view! {
-        <nav>
+        <nav class:hidden=hidden>
             <div class="flex justify-between px-1 text-sm md:text-base">
                 <div>
                     <Show when=book_exists>
-                        <button class="hover:text-sky-500" on:click=move |_| book::unload()>"✕"</button>
+                        <button class="hover:text-link" on:click=move |_| book::unload()>"✕"</button>
                     </Show>
                 </div>
                 <ul class="flex space-x-6 md:space-x-10">
                     <Show when=book_exists fallback=move || view! {
-                        <li><span class:underline=move || nav_state.get() == NavState::Upload><A class="hover:text-sky-500" href="">load</A></span></li> }>
-                        <li><span class:underline=move || nav_state.get() == NavState::Read><A class="hover:text-sky-500" href={move || format!("{}", page.get())}>read</A></span></li>
-                        <li><span class:underline=move || nav_state.get() == NavState::Toc><A class="hover:text-sky-500" href="">table of contents</A></span></li>
+                        <li><span class:underline=move || nav_state.get() == NavState::Upload><A class="hover:text-link" href="">load</A></span></li> }>
+                        <li><span class:underline=move || nav_state.get() == NavState::Read><A class="hover:text-link" href={move || format!("{}", page.get())}>read</A></span></li>
+                        <li><span class:underline=move || nav_state.get() == NavState::Toc><A class="hover:text-link" href="">table of contents</A></span></li>
+                        <li><span class:underline=move || nav_state.get() == NavState::Bookmarks><A class="hover:text-link" href="bookmarks">bookmarks</A></span></li>
+                        <li><span class:underline=move || nav_state.get() == NavState::Search><A class="hover:text-link" href="search">search</A></span></li>
                     </Show>
-                    <li><span class:underline=move || nav_state.get() == NavState::Settings><A class="hover:text-sky-500" href="settings">settings</A></span></li>
+                    <li><span class:underline=move || nav_state.get() == NavState::Library><A class="hover:text-link" href="library">library</A></span></li>
+                    <li><span class:underline=move || nav_state.get() == NavState::Settings><A class="hover:text-link" href="settings">settings</A></span></li>
                 </ul>
             </div>
         </nav>
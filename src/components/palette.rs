@@ -0,0 +1,138 @@
+use std::rc::Rc;
+
+use leptos::*;
+
+use crate::{book::Book, input::Action, palette};
+
+/// The `:` command line, running commands through `dispatch`.
+#[component]
+pub fn Palette(open: RwSignal<bool>, dispatch: Rc<dyn Fn(Action)>) -> impl IntoView {
+    let book = expect_context::<ReadSignal<Book>>();
+    let (line, set_line) = create_signal(String::new());
+    let (error, set_error) = create_signal(None::<String>);
+    // completions being cycled through with tab, and the one shown
+    let cycle = store_value((Vec::<String>::new(), 0));
+    let input_element: NodeRef<html::Input> = create_node_ref();
+
+    create_effect(move |_| {
+        if open.get() {
+            // once the input is shown
+            request_animation_frame(move || {
+                if let Some(input) = input_element.get_untracked() {
+                    let _ = input.focus();
+                }
+            });
+        }
+    });
+
+    let close = move || {
+        open.set(false);
+        set_line.set(String::new());
+        set_error.set(None);
+        cycle.set_value((Vec::new(), 0));
+    };
+
+    let completions = move || {
+        let line = line.get();
+        book.with(|book| {
+            book.as_ref()
+                .map(|book| palette::complete(&line, book))
+                .unwrap_or_default()
+        })
+    };
+
+    let complete = move || {
+        let line = line.get_untracked();
+        let next = cycle.with_value(|(options, idx)| {
+            // keep cycling while the line is still the last completion
+            if options.get(*idx) == Some(&line) {
+                Some((idx + 1) % options.len())
+            } else {
+                None
+            }
+        });
+        match next {
+            Some(idx) => cycle.update_value(|(_, i)| *i = idx),
+            None => {
+                let options = book.with_untracked(|book| {
+                    book.as_ref()
+                        .map(|book| palette::complete(&line, book))
+                        .unwrap_or_default()
+                });
+                cycle.set_value((options, 0));
+            }
+        }
+        if let Some(option) = cycle.with_value(|(options, idx)| options.get(*idx).cloned()) {
+            set_line.set(option);
+        }
+    };
+
+    let on_keydown = move |ev: ev::KeyboardEvent| match ev.key().as_str() {
+        "Escape" => {
+            ev.prevent_default();
+            close();
+        }
+        "Tab" => {
+            ev.prevent_default();
+            complete();
+        }
+        _ => {}
+    };
+
+    let submit = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        let Some(book) = book.get_untracked() else {
+            return;
+        };
+        match palette::parse(&line.get_untracked(), &book) {
+            Ok(action) => {
+                close();
+                dispatch(action);
+            }
+            Err(e) => set_error.set(Some(e)),
+        }
+    };
+
+    let suggestions = move || {
+        completions()
+            .into_iter()
+            .take(8)
+            .map(|option| {
+                let label = option.clone();
+                view! {
+                    <li>
+                        <button class="text-left hover:text-link"
+                                on:click=move |_| {
+                                    set_line.set(option.clone());
+                                    if let Some(input) = input_element.get_untracked() {
+                                        let _ = input.focus();
+                                    }
+                                }>
+                            {label}
+                        </button>
+                    </li>
+                }
+            })
+            .collect_view()
+    };
+
+    view! {
+        <div class="fixed inset-x-0 bottom-0 z-20 flex justify-center"
+             class:hidden=move || !open.get()>
+            <div class="w-full max-w-screen-sm md:max-w-screen-md mx-2 mb-2 p-3
+                        rounded-lg border border-zinc-600 font-sans text-base
+                        bg-page">
+                <ul class="mb-2 text-sm">{suggestions}</ul>
+                <form class="flex items-baseline" on:submit=submit>
+                    <span class="mr-1 font-mono">":"</span>
+                    <input class="flex-1 rounded-lg bg-transparent py-0 font-mono"
+                           node_ref=input_element
+                           prop:value=line
+                           on:input=move |ev| set_line.set(event_target_value(&ev))
+                           on:keydown=on_keydown />
+                </form>
+                <p class="text-sm text-rose-500">{error}</p>
+            </div>
+        </div>
+    }
+}
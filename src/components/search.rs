@@ -0,0 +1,78 @@
+use leptos::*;
+
+use crate::{
+    book::{self, Book},
+    nav_state::NavState,
+    search, set_nav_state,
+};
+
+#[component]
+pub fn Search() -> impl IntoView {
+    set_nav_state(NavState::Search);
+
+    let book = expect_context::<ReadSignal<Book>>();
+    let search = search::get();
+    let input_element: NodeRef<html::Input> = create_node_ref();
+
+    let on_submit = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        let query = input_element.get().unwrap().value();
+        let Some(book) = book.get_untracked() else {
+            return;
+        };
+        search.run(&book, query);
+    };
+
+    let results = move || {
+        let book = book.get()?;
+        let results = search.results.get();
+        if results.is_empty() {
+            let query = search.query.get();
+            return (!query.is_empty()).then(|| view! { <p>"No matches."</p> }.into_view());
+        }
+        let count = results.len();
+        let list = results
+            .into_iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let chapter = book::chapter_name(&book, m.page)
+                    .unwrap_or_else(|| format!("Section {}", m.page + 1));
+                view! {
+                    <li class="pb-4">
+                        <button class="text-left hover:text-link" on:click=move |_| search.go(i)>
+                            <div class="font-sans text-sm">{chapter}</div>
+                            <div class="font-serif">
+                                "…"{m.before}
+                                <mark class="bg-highlight text-current">{m.matched}</mark>
+                                {m.after}"…"
+                            </div>
+                        </button>
+                    </li>
+                }
+            })
+            .collect_view();
+        Some(
+            view! {
+                <p class="mb-4 text-sm">{format!("{count} matches")}</p>
+                <ul>{list}</ul>
+            }
+            .into_view(),
+        )
+    };
+
+    view! {
+        <h1 class="mt-8 mb-10 text-left font-sans font-bold text-2xl md:text-4xl tracking-tight leading-none">
+            Search
+        </h1>
+
+        <form class="mb-8 text-base" on:submit=on_submit>
+            <input type="search"
+                   class="w-full rounded-lg bg-transparent"
+                   placeholder="find in book"
+                   prop:value=move || search.query.get_untracked()
+                   node_ref=input_element />
+        </form>
+
+        <div class="text-base">{results}</div>
+    }
+}
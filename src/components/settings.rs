@@ -1,8 +1,16 @@
 use leptos::*;
 
 use crate::{
-    config,
+    book::{self, Book},
+    components::{Keymap, Typography},
+    config::{self, Config, Layout},
+    export,
+    input::TapAction,
+    library,
     nav_state::{set_nav_state, NavState},
+    overrides::{self, Overrides},
+    storage,
+    theme::{Colors, Theme},
 };
 
 #[component]
@@ -10,12 +18,101 @@ pub fn Settings() -> impl IntoView {
     set_nav_state(NavState::Settings);
 
     let clear_storage = || {
-        if let Ok(Some(storage)) = leptos::window().local_storage() {
-            let _ = storage.clear();
-        }
+        storage::get().clear();
+        spawn_local(async {
+            let _ = library::clear().await;
+        });
+    };
+
+    // bumped when settings change all at once, so the controls show them
+    let generation = create_rw_signal(0);
+    let config = store_value(config::get());
+    let overrides = overrides::get();
+    // replace settings through `f`, which is handed the defaults
+    let replace = move |f: &dyn Fn(&mut Config, Config)| {
+        config.with_value(|c| {
+            f(&mut c.borrow_mut(), Config::default());
+            let c = c.borrow();
+            c.save();
+            if !c.cache_book {
+                book::forget();
+            }
+        });
+        overrides.refresh();
+        generation.update(|g| *g += 1);
+    };
+
+    let export = move |_| {
+        let json = config.with_value(|c| c.borrow().export());
+        let _ = export::download("wepu-settings.json", "application/json", &json);
+    };
+    let (errors, set_errors) = create_signal(Vec::<String>::new());
+    let input_element: NodeRef<html::Input> = create_node_ref();
+    let import = move |_| {
+        let Some(input) = input_element.get() else {
+            return;
+        };
+        let Some(file) = input.files().and_then(|f| f.get(0)) else {
+            return;
+        };
+        // so picking the same file again imports it again
+        input.set_value("");
+        spawn_local(async move {
+            let Ok(json) = wasm_bindgen_futures::JsFuture::from(file.text()).await else {
+                set_errors.set(vec!["could not read the file".to_owned()]);
+                return;
+            };
+            match Config::import(&json.as_string().unwrap_or_default()) {
+                Ok(imported) => {
+                    set_errors.set(Vec::new());
+                    replace(&move |c, _| *c = imported.clone());
+                }
+                Err(errors) => set_errors.set(errors),
+            }
+        });
     };
 
+    view! {
+        <h1 class="mt-8 mb-10 text-left font-sans font-bold text-2xl md:text-4xl tracking-tight leading-none">
+            Settings
+        </h1>
+
+        {move || {
+            generation.track();
+            view! { <Sections replace=replace /> }
+        }}
+
+        <h2 class="mt-8 mb-4 font-sans font-bold text-xl">Settings file</h2>
+        <div class="flex flex-col text-base space-y-3">
+            <p class="text-sm">"Move settings to another device by exporting them to a file, then importing it there."</p>
+            <div class="space-x-6">
+                <button class="hover:text-link" on:click=export>"export"</button>
+                <button class="hover:text-link" on:click=move |_| input_element.get().unwrap().click()>"import"</button>
+                <button class="hover:text-link" on:click=move |_| replace(&|c, default| *c = default)>
+                    "reset everything"
+                </button>
+            </div>
+            <input class="hidden" tabindex=-1 type="file" accept="application/json,.json"
+                   node_ref=input_element on:change=import />
+            <ul class="text-sm text-rose-500">
+                {move || errors.get().into_iter().map(|e| view! { <li>{e}</li> }).collect_view()}
+            </ul>
+            <div><button class="bg-ink text-page mt-2 active:bg-sky-500 rounded-lg px-3 py-1" on:click=move |_| clear_storage()>Clear data</button></div>
+        </div>
+    }
+}
+
+// controls of every setting, read from the config when created
+#[component]
+fn Sections<F>(replace: F) -> impl IntoView
+where
+    F: Fn(&dyn Fn(&mut Config, Config)) + Copy + 'static,
+{
     let config = config::get();
+    // rendering settings show what the open book is rendered with
+    let overrides = overrides::get();
+    let shown = overrides.settings().get_untracked();
+    let book = expect_context::<ReadSignal<Book>>();
     let config_ = config.clone();
 
     let save_position = move |ev| {
@@ -28,17 +125,120 @@ pub fn Settings() -> impl IntoView {
         config_.borrow_mut().cache_book = checked;
         config_.borrow().save();
         if !checked {
-            if let Ok(Some(storage)) = leptos::window().local_storage() {
-                let _ = storage.remove_item("b");
-            }
+            book::forget();
         }
     };
 
-    view! {
-        <h1 class="mt-8 mb-10 text-left font-sans font-bold text-2xl md:text-4xl tracking-tight leading-none">
-            Settings
-        </h1>
+    let current_theme = create_rw_signal(shown.theme);
+    let theme = move |ev| {
+        let Some(theme) = Theme::parse(&event_target_value(&ev)) else {
+            return;
+        };
+        overrides.update("theme", |c| c.theme = theme);
+        current_theme.set(theme);
+    };
+    let theme_options = Theme::ALL
+        .into_iter()
+        .map(|t| view! { <option value=t.name() selected=move || t == current_theme.get()>{t.name()}</option> })
+        .collect_view();
+
+    // picking a color switches to the custom theme
+    let color = |label: &'static str, field: fn(&mut Colors) -> &mut String| {
+        let set = move |ev| {
+            let value = event_target_value(&ev);
+            overrides.update("colors", |c| {
+                *field(&mut c.colors) = value;
+                c.theme = Theme::Custom;
+            });
+            current_theme.set(Theme::Custom);
+        };
+        let value = field(&mut shown.colors.clone()).clone();
+        view! {
+            <label class="inline-flex items-center">
+                <input type="color" class="w-8 h-6 p-0 border-0 bg-transparent" value=value on:input=set />
+                <span class="ml-2">{label}</span>
+            </label>
+        }
+    };
+
+    let layout = move |ev| {
+        let Some(layout) = Layout::parse(&event_target_value(&ev)) else {
+            return;
+        };
+        overrides.update("layout", |c| c.layout = layout);
+    };
+    let current_layout = shown.layout;
+    let layout_options = Layout::ALL
+        .into_iter()
+        .map(|l| view! { <option value=l.name() selected=l == current_layout>{l.name()}</option> })
+        .collect_view();
+
+    let spread =
+        move |ev: ev::Event| overrides.update("spread", |c| c.spread = event_target_checked(&ev));
+
+    let tap_zone = |zone: usize, label: &'static str| {
+        let config_ = config.clone();
+        let set = move |ev| {
+            let Some(action) = TapAction::parse(&event_target_value(&ev)) else {
+                return;
+            };
+            config_.borrow_mut().tap[zone] = action;
+            config_.borrow().save();
+        };
+        let current = config.borrow().tap[zone];
+        let options = TapAction::ALL
+            .into_iter()
+            .map(|t| view! { <option value=t.name() selected=t == current>{t.description()}</option> })
+            .collect_view();
+        view! {
+            <label>
+                <span class="mr-2">{label}</span>
+                <select class="rounded-xs bg-transparent text-base py-0" on:change=set>
+                    {options}
+                </select>
+            </label>
+        }
+    };
+    let config_ = config.clone();
+    let swipe = move |ev| {
+        config_.borrow_mut().swipe = event_target_checked(&ev);
+        config_.borrow().save();
+    };
 
+    // whether `field`, with those grouped with it, is set for every book or
+    // only the open one
+    let scope = move |field: &'static str| {
+        let title = book.with_untracked(|b| b.as_ref().map(|b| b.title().to_owned()))?;
+        let overridden = overrides.overrides(field);
+        let toggle = move |ev| {
+            overrides.set_overrides(field, event_target_checked(&ev));
+            // show the values now in effect
+            replace(&|_, _| {});
+        };
+        Some(view! {
+            <label class="inline-flex items-center text-sm">
+                <input type="checkbox" class="rounded-xs text-amber-500" checked=overridden on:input=toggle />
+                <span class="ml-2">
+                    {if overridden { "Only for " } else { "For every book, or only for " }}
+                    <i>{title}</i>
+                </span>
+            </label>
+        })
+    };
+
+    // reset a section where its settings are kept, so settings the open book
+    // overrides are reset for it alone
+    let reset = move |f: fn(Overrides, Config)| {
+        let click = move |_| {
+            f(overrides, Config::default());
+            replace(&|_, _| {});
+        };
+        view! {
+            <div><button class="text-sm hover:text-link" on:click=click>"reset to defaults"</button></div>
+        }
+    };
+
+    view! {
         <div class="flex flex-col justify-center text-base space-y-3">
         <label class="inline-flex items-center">
             <input type="checkbox" class="rounded-xs text-sky-500" id="save-position" checked={config.borrow().save_position} on:input=save_position/>
@@ -46,9 +246,72 @@ pub fn Settings() -> impl IntoView {
         </label>
         <label class="inline-flex items-center">
             <input type="checkbox" class="rounded-xs text-sky-500" id="cache-book" checked={config.borrow().cache_book} on:input=cache_book/>
-            <span class="ml-2">"Save most recent book between sessions (if book smaller than 3 megabytes)"</span>
+            <span class="ml-2">"Reopen the most recent book between sessions"</span>
         </label>
-            <div><button class="bg-sepia-dark text-sepia-light active:text-sepia-light dark:bg-zinc-200 dark:text-zinc-800 mt-2 active:bg-sky-500 dark:active:text-zinc-200 rounded-lg px-3 py-1" on:click=move |_| clear_storage()>Clear data</button></div>
+        <label>
+            <span class="mr-2">Chapters are</span>
+            <select class="rounded-xs bg-transparent text-base py-0" on:change=layout>
+                {layout_options}
+            </select>
+        </label>
+        {scope("layout")}
+        <label class="inline-flex items-center">
+            <input type="checkbox" class="rounded-xs text-sky-500" id="spread" checked=shown.spread on:input=spread/>
+            <span class="ml-2">"Show two pages side by side on wide screens, when paginated"</span>
+        </label>
+        {scope("spread")}
+        {reset(|overrides, default| {
+            overrides.update("save_position", |c| {
+                c.save_position = default.save_position;
+                c.cache_book = default.cache_book;
+            });
+            overrides.update("layout", |c| c.layout = default.layout);
+            overrides.update("spread", |c| c.spread = default.spread);
+        })}
+        <span class="pt-3 font-bold">Theme</span>
+        <label>
+            <span class="mr-2">Theme</span>
+            <select class="rounded-xs bg-transparent text-base py-0" on:change=theme>
+                {theme_options}
+            </select>
+        </label>
+        <div class="flex flex-wrap gap-x-6 gap-y-3">
+            {color("Background", |c| &mut c.background)}
+            {color("Text", |c| &mut c.text)}
+            {color("Links", |c| &mut c.link)}
+            {color("Highlights", |c| &mut c.highlight)}
+        </div>
+        {scope("theme")}
+        {reset(|overrides, default| {
+            overrides.update("theme", |c| {
+                c.theme = default.theme;
+                c.colors = default.colors;
+            });
+        })}
+        <span class="pt-3 font-bold">Touch</span>
+        {tap_zone(0, "Tapping the left third")}
+        {tap_zone(1, "Tapping the middle third")}
+        {tap_zone(2, "Tapping the right third")}
+        <label class="inline-flex items-center">
+            <input type="checkbox" class="rounded-xs text-sky-500" id="swipe" checked={config.borrow().swipe} on:input=swipe/>
+            <span class="ml-2">Swipe left or right to change chapter</span>
+        </label>
+        {reset(|overrides, default| {
+            overrides.update("tap", |c| {
+                c.tap = default.tap;
+                c.swipe = default.swipe;
+            });
+        })}
+        </div>
+
+        <div class="text-base">
+            <Typography />
+            <div class="mt-3">{scope("typography")}</div>
+            {reset(|overrides, default| overrides.update("typography", |c| c.typography = default.typography))}
+        </div>
+
+        <div class="text-base">
+            <Keymap />
         </div>
     }
 }
@@ -2,7 +2,14 @@ use leptos::*;
 use leptos_router::A;
 use lepu::Chapter;
 
-use crate::{book::Book, nav_state::NavState, set_nav_state};
+use crate::{
+    annotations,
+    book::Book,
+    export::{self, Format},
+    nav_state::NavState,
+    position::{self, GlobalMarks},
+    set_nav_state, Marks,
+};
 
 fn make_list<'a>(entries: impl Iterator<Item = &'a Chapter>) -> leptos::View {
     let page = expect_context::<ReadSignal<usize>>();
@@ -17,7 +24,7 @@ fn make_list<'a>(entries: impl Iterator<Item = &'a Chapter>) -> leptos::View {
                 "pb-2"
             };
             view! {
-                <li><div class=class><A href=idx class="hover:text-sky-500">{name}</A></div>
+                <li><div class=class on:click=|_| position::record_jump()><A href=idx class="hover:text-link">{name}</A></div>
                     {sublist}
                 </li>
             }
@@ -32,13 +39,33 @@ pub fn Toc() -> impl IntoView {
     set_nav_state(NavState::Toc);
 
     let book = expect_context::<ReadSignal<Book>>();
+    let annotations = annotations::get();
+    let marks = expect_context::<Marks>();
+    let global_marks = expect_context::<GlobalMarks>();
+    let export = move |format: Format| {
+        let Some(book) = book.get_untracked() else {
+            return;
+        };
+        annotations.highlights.with_untracked(|highlights| {
+            let marks = position::book_marks(book.identifier(), &marks, &global_marks);
+            export::notes(&book, highlights, &marks, format);
+        });
+    };
+
     view! {
         { move || {
             let book = book.get().unwrap();
+            let markdown = export.clone();
+            let json = export.clone();
             view! {
                 <div class="mt-8 mb-10 text-left font-bold text-2xl md:text-4xl tracking-tight leading-none">
                     <h1>{book.title().to_owned()}</h1>
                 </div>
+                <div class="-mt-6 mb-8 font-sans text-sm space-x-3">
+                    <span>"export notes:"</span>
+                    <button class="hover:text-link" on:click=move |_| markdown(Format::Markdown)>"markdown"</button>
+                    <button class="hover:text-link" on:click=move |_| json(Format::Json)>"json"</button>
+                </div>
                 <div class="text-justify font-serif tracking-tight leading-tight">
                     {make_list(book.chapters())}
                 </div>
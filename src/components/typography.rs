@@ -0,0 +1,118 @@
+use leptos::*;
+
+use crate::{
+    overrides,
+    typography::{self, Font},
+};
+
+const PREVIEW: [&str; 2] = [
+    "It was the best of times, it was the worst of times, it was the age of wisdom, it was \
+     the age of foolishness, it was the epoch of belief, it was the epoch of incredulity, it \
+     was the season of Light, it was the season of Darkness.",
+    "It was the spring of hope, it was the winter of despair, we had everything before us, \
+     we had nothing before us, we were all going direct to Heaven, we were all going direct \
+     the other way.",
+];
+
+const COLUMN_WIDTHS: [u32; 6] = [30, 35, 40, 45, 50, 60];
+
+/// Typography controls, with a preview of the text they set.
+#[component]
+pub fn Typography() -> impl IntoView {
+    // that of the open book, if it has its own
+    let overrides = overrides::get();
+    let typography = create_rw_signal(
+        overrides
+            .settings()
+            .with_untracked(|s| s.typography.clone()),
+    );
+
+    create_effect(move |first| {
+        let typography = typography.get();
+        // only save once something actually changed
+        if first.is_some() {
+            overrides.update("typography", |c| c.typography = typography);
+        }
+    });
+
+    let set = move |f: fn(&mut typography::Typography, String)| {
+        move |ev: ev::Event| typography.update(|t| f(t, event_target_value(&ev)))
+    };
+    let check = move |f: fn(&mut typography::Typography, bool)| {
+        move |ev: ev::Event| typography.update(|t| f(t, event_target_checked(&ev)))
+    };
+
+    let current = typography.get_untracked();
+    let fonts = Font::ALL
+        .into_iter()
+        .map(|f| view! { <option value=f.name() selected=f == current.font>{f.description()}</option> })
+        .collect_view();
+    let widths = COLUMN_WIDTHS
+        .into_iter()
+        .map(|w| {
+            let selected = current.column_width == Some(w);
+            view! { <option value=w selected=selected>{format!("{w} em")}</option> }
+        })
+        .collect_view();
+
+    let preview = PREVIEW
+        .into_iter()
+        .map(|p| view! { <p>{p}</p> })
+        .collect_view();
+
+    view! {
+        <h2 class="mt-8 mb-4 font-sans font-bold text-xl">Typography</h2>
+        <div class="flex flex-col space-y-3">
+            <label>
+                <span class="mr-2">Font</span>
+                <select class="rounded-xs bg-transparent text-base py-0"
+                        on:change=set(|t, v| t.font = Font::parse(&v).unwrap_or(t.font))>
+                    {fonts}
+                </select>
+            </label>
+            <label class="flex items-center space-x-3">
+                <span>Size</span>
+                <input type="range" min="70" max="200" step="5" class="flex-1"
+                       prop:value=move || typography.with(|t| t.scale)
+                       on:input=set(|t, v| t.scale = v.parse().unwrap_or(t.scale)) />
+                <span class="w-14 text-right">{move || typography.with(|t| format!("{}%", t.scale))}</span>
+            </label>
+            <label class="flex items-center space-x-3">
+                <span>Line height</span>
+                <input type="range" min="1" max="2.4" step="0.05" class="flex-1"
+                       prop:value=move || typography.with(|t| t.line_height)
+                       on:input=set(|t, v| t.line_height = v.parse().unwrap_or(t.line_height)) />
+                <span class="w-14 text-right">{move || typography.with(|t| format!("{:.2}", t.line_height))}</span>
+            </label>
+            <label class="flex items-center space-x-3">
+                <span>Paragraph spacing</span>
+                <input type="range" min="0" max="2" step="0.25" class="flex-1"
+                       prop:value=move || typography.with(|t| t.paragraph_spacing)
+                       on:input=set(|t, v| t.paragraph_spacing = v.parse().unwrap_or(t.paragraph_spacing)) />
+                <span class="w-14 text-right">{move || typography.with(|t| format!("{:.2}", t.paragraph_spacing))}</span>
+            </label>
+            <label>
+                <span class="mr-2">Column width</span>
+                <select class="rounded-xs bg-transparent text-base py-0"
+                        on:change=set(|t, v| t.column_width = v.parse().ok())>
+                    <option value="full" selected=current.column_width.is_none()>"as wide as the page"</option>
+                    {widths}
+                </select>
+            </label>
+            <label class="inline-flex items-center">
+                <input type="checkbox" class="rounded-xs text-sky-500" checked=current.justify
+                       on:input=check(|t, c| t.justify = c) />
+                <span class="ml-2">Justify text</span>
+            </label>
+            <label class="inline-flex items-center">
+                <input type="checkbox" class="rounded-xs text-sky-500" checked=current.hyphenate
+                       on:input=check(|t, c| t.hyphenate = c) />
+                <span class="ml-2">Hyphenate words</span>
+            </label>
+            <div class="p-3 rounded-lg border border-zinc-600 text-base sm:text-lg md:text-2xl"
+                 style=move || typography.with(|t| t.style())>
+                <div class="space-y-[var(--para-gap)]">{preview}</div>
+            </div>
+        </div>
+    }
+}
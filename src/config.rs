@@ -1,11 +1,119 @@
 use std::{cell::RefCell, rc::Rc};
 
 use leptos::{expect_context, provide_context};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 
-#[derive(Debug)]
+use crate::{
+    input::TapAction,
+    keymap::Keymap,
+    storage::{self, Key, Storage},
+    theme::{self, Colors, Theme},
+    typography::Typography,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// A chapter is one long column.
+    Scroll,
+    /// A chapter is split into screens, turned like pages.
+    Paginated,
+    /// Chapters follow each other in one long column.
+    Continuous,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 3] = [Layout::Scroll, Layout::Paginated, Layout::Continuous];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Scroll => "scroll",
+            Layout::Paginated => "paginated",
+            Layout::Continuous => "continuous",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Layout> {
+        Layout::ALL.into_iter().find(|l| l.name() == name)
+    }
+}
+
+/// Makes deserializers that fall back on the default of a field, instead of
+/// failing, when its value has the wrong type or isn't `$valid`, so one bad
+/// setting doesn't cost the others.
+macro_rules! lenient {
+    ($module:ident for $ty:ident { $($field:ident: $field_ty:ty $(where $valid:expr)?),* $(,)? }) => {
+        mod $module {
+            use super::*;
+
+            $(
+                pub fn $field<'de, D: ::serde::Deserializer<'de>>(
+                    deserializer: D,
+                ) -> Result<$field_ty, D::Error> {
+                    let value: ::serde_json::Value =
+                        ::serde::Deserialize::deserialize(deserializer)?;
+                    Ok(<$field_ty as ::serde::Deserialize>::deserialize(value)
+                        .ok()
+                        $(.filter($valid))?
+                        .unwrap_or_else(|| $ty::default().$field))
+                }
+            )*
+        }
+    };
+}
+pub(crate) use lenient;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
+    #[serde(deserialize_with = "or_default::save_position")]
     pub save_position: bool,
+    #[serde(deserialize_with = "or_default::cache_book")]
     pub cache_book: bool,
+    #[serde(deserialize_with = "or_default::keymap")]
+    pub keymap: Keymap,
+    /// Tap zones: left, center and right third of the page.
+    #[serde(deserialize_with = "tap")]
+    pub tap: [TapAction; 3],
+    /// Horizontal swipes change chapter.
+    #[serde(deserialize_with = "or_default::swipe")]
+    pub swipe: bool,
+    #[serde(deserialize_with = "or_default::layout")]
+    pub layout: Layout,
+    /// Two screens side by side when paginated on a wide screen.
+    #[serde(deserialize_with = "or_default::spread")]
+    pub spread: bool,
+    #[serde(deserialize_with = "or_default::theme")]
+    pub theme: Theme,
+    /// Colors of `Theme::Custom`.
+    #[serde(deserialize_with = "or_default::colors")]
+    pub colors: Colors,
+    #[serde(deserialize_with = "or_default::typography")]
+    pub typography: Typography,
+}
+
+lenient!(or_default for Config {
+    save_position: bool,
+    cache_book: bool,
+    keymap: Keymap,
+    swipe: bool,
+    layout: Layout,
+    spread: bool,
+    theme: Theme,
+    colors: Colors,
+    typography: Typography,
+});
+
+// each zone on its own, like the other settings
+fn tap<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[TapAction; 3], D::Error> {
+    let mut tap = Config::default().tap;
+    if let Value::Array(values) = Value::deserialize(deserializer)? {
+        for (zone, value) in tap.iter_mut().zip(values) {
+            *zone = TapAction::deserialize(value).unwrap_or(*zone);
+        }
+    }
+    Ok(tap)
 }
 
 impl Default for Config {
@@ -13,42 +121,434 @@ impl Default for Config {
         Self {
             save_position: true,
             cache_book: false,
+            keymap: Keymap::default(),
+            tap: [
+                TapAction::PreviousPage,
+                TapAction::ToggleNavBar,
+                TapAction::NextPage,
+            ],
+            swipe: true,
+            layout: Layout::Scroll,
+            spread: false,
+            theme: Theme::System,
+            colors: Colors::default(),
+            typography: Typography::default(),
         }
     }
 }
 
+// bumped whenever a stored field changes meaning, so values written before
+// can be told apart
+const VERSION: u64 = 1;
+
+// what `to_json` writes: the settings tagged with the format's version
+#[derive(Serialize)]
+struct Versioned<'a> {
+    version: u64,
+    #[serde(flatten)]
+    config: &'a Config,
+}
+
 impl Config {
+    pub fn apply_theme(&self) {
+        theme::apply(self.theme, &self.colors);
+    }
+
     pub fn save(&self) {
-        let Ok(Some(storage)) = leptos::window().local_storage() else {
-            return;
+        storage::get().set(Key::Config, &self.to_json());
+    }
+
+    /// Every setting in a json object, tagged with the format's version.
+    pub fn to_json(&self) -> String {
+        self.to_value().to_string()
+    }
+
+    /// `to_json`, laid out to be read by people, for settings files.
+    pub fn export(&self) -> String {
+        serde_json::to_string_pretty(&self.to_value()).unwrap_or_default()
+    }
+
+    pub fn to_value(&self) -> Value {
+        let versioned = Versioned {
+            version: VERSION,
+            config: self,
         };
+        serde_json::to_value(versioned).unwrap_or_default()
+    }
 
-        let config_string = format!("{}:{}", self.save_position, self.cache_book);
-        let _ = storage.set_item("c", &config_string);
+    /// Read settings written by `to_json`. Settings that are missing or
+    /// invalid keep their defaults and unknown ones are ignored, so configs
+    /// written by older and newer versions load as far as they are
+    /// understood. `None` if `json` isn't an object at all.
+    pub fn from_json(json: &str) -> Option<Config> {
+        Config::from_value(&serde_json::from_str(json).ok()?)
+    }
+
+    /// `from_json`, from parsed json.
+    pub fn from_value(value: &Value) -> Option<Config> {
+        match value {
+            Value::Object(_) => Config::deserialize(value).ok(),
+            _ => None,
+        }
+    }
+
+    /// Read a settings file written by `export`. Unlike `from_json` this
+    /// rejects anything it doesn't understand, listing what's wrong.
+    pub fn import(json: &str) -> Result<Config, Vec<String>> {
+        let input: Value =
+            serde_json::from_str(json).map_err(|e| vec![format!("not a settings file: {e}")])?;
+        let version = match &input {
+            Value::Object(fields) => fields.get("version").and_then(Value::as_u64),
+            _ => {
+                return Err(vec![
+                    "not a settings file: expected a json object".to_owned()
+                ])
+            }
+        };
+        match version {
+            Some(1..=VERSION) => {}
+            Some(version) => {
+                return Err(vec![format!(
+                    "settings are from a newer version of wepu (version {version})"
+                )])
+            }
+            None => return Err(vec!["not a settings file: missing version".to_owned()]),
+        }
+
+        let config = Config::from_value(&input).unwrap_or_default();
+        let mut errors = Vec::new();
+        not_read(&input, &config.to_value(), "", &mut errors);
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(errors)
+        }
     }
 }
 
-fn load() -> Option<Config> {
-    let Ok(Some(storage)) = leptos::window().local_storage() else {
-        return None;
-    };
-    let Ok(Some(config_string)) = storage.get_item("c") else {
-        return None;
-    };
+// settings in `input` that didn't make it into `read`, which is what was
+// read from it written out again
+fn not_read(input: &Value, read: &Value, path: &str, errors: &mut Vec<String>) {
+    match (input, read) {
+        (Value::Object(input), Value::Object(read)) => {
+            for (name, value) in input {
+                let path = match path {
+                    "" => name.clone(),
+                    _ => format!("{path}.{name}"),
+                };
+                match read.get(name) {
+                    Some(read) => not_read(value, read, &path, errors),
+                    None => errors.push(format!("unknown setting \"{path}\"")),
+                }
+            }
+        }
+        // 2 and 2.0 are the same line height
+        (Value::Number(a), Value::Number(b)) if a.as_f64() == b.as_f64() => {}
+        (input, read) if input == read => {}
+        _ => errors.push(format!("invalid value {input} for \"{path}\"")),
+    }
+}
 
-    let (save_position, cache_book) = config_string.split_once(':')?;
+// the format before `to_json`: "c" held the fields below separated by
+// colons, with keys, typography and custom colors in "k", "t" and "s"
+fn migrate(c: &str, k: Option<&str>, t: Option<&str>, s: Option<&str>) -> Option<Config> {
+    let mut fields = c.split(':');
+    let save_position = fields.next()?.parse::<bool>().ok()?;
+    let cache_book = fields.next()?.parse::<bool>().ok()?;
+    // fields added later may be missing
+    let default = Config::default();
+    let mut tap = default.tap;
+    for zone in &mut tap {
+        if let Some(action) = fields.next().and_then(TapAction::parse) {
+            *zone = action;
+        }
+    }
+    let swipe = fields
+        .next()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(default.swipe);
+    let layout = fields
+        .next()
+        .and_then(Layout::parse)
+        .unwrap_or(default.layout);
+    let spread = fields
+        .next()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(default.spread);
+    let theme = fields
+        .next()
+        .and_then(Theme::parse)
+        .unwrap_or(default.theme);
     Some(Config {
-        save_position: save_position.parse::<bool>().ok()?,
-        cache_book: cache_book.parse::<bool>().ok()?,
+        save_position,
+        cache_book,
+        keymap: k.and_then(Keymap::from_legacy).unwrap_or_default(),
+        tap,
+        swipe,
+        layout,
+        spread,
+        theme,
+        colors: s.and_then(Colors::from_legacy).unwrap_or_default(),
+        typography: t.and_then(Typography::from_legacy).unwrap_or_default(),
     })
 }
 
+fn load(storage: &Storage) -> Option<Config> {
+    if let Some(json) = storage.get(Key::Config) {
+        return Config::from_json(&json);
+    }
+
+    let c = storage.get(Key::Legacy("c"))?;
+    let get = |key| storage.get(Key::Legacy(key));
+    let config = migrate(
+        &c,
+        get("k").as_deref(),
+        get("t").as_deref(),
+        get("s").as_deref(),
+    );
+    // only once, the old keys aren't read again
+    if let Some(config) = &config {
+        storage.set(Key::Config, &config.to_json());
+        for key in ["c", "k", "t", "s"] {
+            storage.remove(Key::Legacy(key));
+        }
+    }
+    config
+}
+
 pub fn init() -> Rc<RefCell<Config>> {
-    let config = Rc::new(RefCell::new(load().unwrap_or_default()));
+    let config = Rc::new(RefCell::new(load(&storage::get()).unwrap_or_default()));
     provide_context(config.clone());
+
+    // books may override the theme, see `overrides::init`
+    config.borrow().apply_theme();
+
     config
 }
 
 pub fn get() -> Rc<RefCell<Config>> {
     expect_context::<Rc<RefCell<Config>>>()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{keymap::Command, storage::Memory, typography::Font};
+
+    fn changed() -> Config {
+        let mut keymap = Keymap::default();
+        keymap.rebind(Command::NextPage, vec!["l".to_owned(), "<C-n>".to_owned()]);
+        keymap.rebind(Command::Help, Vec::new());
+        Config {
+            save_position: false,
+            cache_book: true,
+            keymap,
+            tap: [
+                TapAction::Nothing,
+                TapAction::NextPage,
+                TapAction::PreviousPage,
+            ],
+            swipe: false,
+            layout: Layout::Paginated,
+            spread: true,
+            theme: Theme::Custom,
+            colors: Colors {
+                background: "#102030".to_owned(),
+                ..Colors::default()
+            },
+            typography: Typography {
+                font: Font::Mono,
+                scale: 130,
+                line_height: 1.8,
+                column_width: Some(40),
+                justify: false,
+                ..Typography::default()
+            },
+        }
+    }
+
+    // the order of bindings doesn't matter
+    fn assert_same(a: Config, b: Config) {
+        let sorted = |keymap: &Keymap| {
+            let mut bindings = keymap.bindings.clone();
+            bindings.sort();
+            bindings
+        };
+        assert_eq!(sorted(&a.keymap), sorted(&b.keymap));
+        let keymap = Keymap::default();
+        assert_eq!(
+            Config {
+                keymap: keymap.clone(),
+                ..a
+            },
+            Config { keymap, ..b }
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let default = Config::default();
+        assert_eq!(Config::from_json(&default.to_json()), Some(default));
+        assert_same(Config::from_json(&changed().to_json()).unwrap(), changed());
+    }
+
+    #[test]
+    fn versioned() {
+        let json: Value = serde_json::from_str(&Config::default().to_json()).unwrap();
+        assert_eq!(json["version"], VERSION);
+    }
+
+    #[test]
+    fn missing_fields_are_defaults() {
+        assert_eq!(Config::from_json("{}"), Some(Config::default()));
+
+        let config =
+            Config::from_json(r#"{"version": 1, "swipe": false, "typography": {"scale": 120}}"#)
+                .unwrap();
+        assert!(!config.swipe);
+        assert_eq!(config.typography.scale, 120);
+        assert_eq!(config.typography.font, Font::Serif);
+        assert_eq!(config.keymap, Keymap::default());
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored() {
+        let config =
+            Config::from_json(r#"{"version": 7, "spread": true, "added later": [1, 2]}"#).unwrap();
+        assert!(config.spread);
+    }
+
+    #[test]
+    fn invalid_fields_are_defaults() {
+        let json = r##"{
+            "save_position": "no",
+            "cache_book": true,
+            "theme": "purple",
+            "colors": {"background": "red", "text": "#000000"},
+            "tap": ["nothing", 3],
+            "layout": null,
+            "typography": {"scale": -5, "font": "mono"},
+            "keymap": {"next-page": ["<Nope>", "l"], "help": "?"}
+        }"##;
+        let config = Config::from_json(json).unwrap();
+        let default = Config::default();
+        assert_eq!(config.save_position, default.save_position);
+        assert!(config.cache_book);
+        assert_eq!(config.theme, default.theme);
+        assert_eq!(config.colors.background, default.colors.background);
+        assert_eq!(config.colors.text, "#000000");
+        assert_eq!(
+            config.tap,
+            [TapAction::Nothing, default.tap[1], default.tap[2]]
+        );
+        assert_eq!(config.layout, default.layout);
+        assert_eq!(config.typography.scale, default.typography.scale);
+        assert_eq!(config.typography.font, Font::Mono);
+        assert_eq!(
+            config
+                .keymap
+                .sequences(Command::NextPage)
+                .collect::<Vec<_>>(),
+            ["l"]
+        );
+        assert_eq!(
+            config.keymap.sequences(Command::Help).collect::<Vec<_>>(),
+            ["?"]
+        );
+    }
+
+    #[test]
+    fn not_an_object() {
+        assert_eq!(Config::from_json(""), None);
+        assert_eq!(Config::from_json("true:false"), None);
+        assert_eq!(Config::from_json("[]"), None);
+    }
+
+    #[test]
+    fn import_export() {
+        let changed = changed();
+        assert_same(Config::import(&changed.export()).unwrap(), changed);
+        // numbers may be written either way
+        let config = Config::import(r#"{"version": 1, "typography": {"line_height": 2}}"#).unwrap();
+        assert_eq!(config.typography.line_height, 2.0);
+    }
+
+    #[test]
+    fn import_errors() {
+        assert_eq!(Config::import("{").unwrap_err().len(), 1);
+        assert_eq!(
+            Config::import("[]").unwrap_err(),
+            ["not a settings file: expected a json object"]
+        );
+        assert_eq!(
+            Config::import(r#"{"swipe": false}"#).unwrap_err(),
+            ["not a settings file: missing version"]
+        );
+        assert_eq!(
+            Config::import(r#"{"version": 2}"#).unwrap_err(),
+            ["settings are from a newer version of wepu (version 2)"]
+        );
+
+        let json = r##"{
+            "version": 1,
+            "theme": "purple",
+            "colors": {"background": "#000000", "text": "black"},
+            "keymap": {"help": ["<Nope>"]},
+            "fullscreen": true
+        }"##;
+        assert_eq!(
+            Config::import(json).unwrap_err(),
+            // in the order of the settings' names
+            [
+                "invalid value \"black\" for \"colors.text\"",
+                "unknown setting \"fullscreen\"",
+                "invalid value [\"<Nope>\"] for \"keymap.help\"",
+                "invalid value \"purple\" for \"theme\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn migrate_first_format() {
+        let config = migrate("false:true", None, None, None).unwrap();
+        assert!(!config.save_position);
+        assert!(config.cache_book);
+        assert_eq!(config.theme, Theme::System);
+        assert_eq!(config.layout, Layout::Scroll);
+    }
+
+    #[test]
+    fn migrate_every_key() {
+        let c = "false:true:nothing:next-page:previous-page:false:paginated:true:custom";
+        let k = "l next-page\n<C-n> next-page\n help\n";
+        let t = "mono:130:1.8:0.75:40:false:false";
+        let s = "#102030:#34281c:#0ea5e9:#0ea5e9";
+        assert_same(migrate(c, Some(k), Some(t), Some(s)).unwrap(), changed());
+    }
+
+    #[test]
+    fn load_migrates_once() {
+        let storage = Storage::new(Memory::default());
+        assert_eq!(load(&storage), None);
+
+        storage.set(Key::Legacy("c"), "false:true");
+        storage.set(Key::Legacy("t"), "mono:130:1.8:0.75:40:false:false");
+        let config = load(&storage).unwrap();
+        assert!(!config.save_position);
+        assert_eq!(config.typography.font, Font::Mono);
+        assert_eq!(storage.get(Key::Legacy("c")), None);
+        assert_eq!(storage.get(Key::Legacy("t")), None);
+        assert_eq!(load(&storage), Some(config));
+    }
+
+    #[test]
+    fn migrate_invalid() {
+        assert_eq!(migrate("", None, None, None), None);
+        assert_eq!(migrate("yes:no", None, None, None), None);
+        // the other keys fall back on their own
+        let config = migrate("true:false", Some("l nonsense"), Some("?"), Some("#fff")).unwrap();
+        assert_eq!(config.keymap, Keymap::default());
+        assert_eq!(config.typography, Typography::default());
+        assert_eq!(config.colors, Colors::default());
+    }
+}
@@ -2,25 +2,100 @@ use std::rc::Rc;
 
 use base64::prelude::*;
 use leptos::*;
-use lepu::{Content, Style, Text, TextKind};
+use lepu::{Content, Epub, Style, Text, TextKind};
 
-use crate::{book::Book, position};
+use crate::{annotations, book::Book, position, search};
+
+/// A range of characters within a paragraph's text to render highlighted.
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub class: &'static str,
+    /// Highlight the span belongs to, exposed as `data-h` on the element.
+    pub highlight: Option<u64>,
+}
+
+/// Element id of paragraph `para` of `page`, unique even with several pages
+/// rendered at once.
+pub fn para_id(page: usize, para: usize) -> String {
+    format!("{page}-{para}")
+}
+
+/// Inverse of `para_id`.
+pub fn parse_para_id(id: &str) -> Option<(usize, usize)> {
+    let (page, para) = id.split_once('-')?;
+    Some((page.parse().ok()?, para.parse().ok()?))
+}
+
+/// Element `page` was rendered into.
+pub fn page_element(page: usize) -> Option<web_sys::Element> {
+    document()
+        .query_selector(&format!("[data-page=\"{page}\"]"))
+        .ok()?
+}
 
 pub fn chapter_to_html(
     tracker: Rc<position::Tracker>,
     book: ReadSignal<Book>,
-    page: ReadSignal<usize>,
+    page: usize,
 ) -> impl IntoView {
     let mut out: Vec<View> = Vec::new();
 
     let mut id = 0;
     let book = book.get().unwrap();
-    let page = page.get();
+    let found = search::get().highlighted(page);
+    let highlights = annotations::get().highlights;
     book.traverse_chapter(page, |ctx, content, _| {
         let view = match content {
-            Content::Textual(tc) => convert(&tc).into_view(),
+            Content::Textual(tc) => {
+                let para = id;
+                let found = found
+                    .iter()
+                    .filter(|m| m.para == para)
+                    .map(|m| (m.start, m.end))
+                    .collect::<Vec<_>>();
+                // only what's drawn, so editing a note doesn't render the
+                // paragraph again, and other edits render only the paragraphs
+                // they touch
+                let ranges = create_memo(move |_| {
+                    highlights.with(|hs| {
+                        hs.iter()
+                            .filter(|h| h.page == page)
+                            .filter_map(|h| {
+                                let (start, end) = h.range_in(para)?;
+                                Some((start, end, h.color, h.id))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                });
+                let paragraph = Paragraph::new(&tc);
+                (move || {
+                    let found = found.iter().map(|&(start, end)| Span {
+                        start,
+                        end,
+                        class: "bg-highlight text-current",
+                        highlight: None,
+                    });
+                    let highlights = ranges
+                        .get()
+                        .into_iter()
+                        .map(|(start, end, color, id)| Span {
+                            start,
+                            end,
+                            class: color.class(),
+                            highlight: Some(id),
+                        });
+                    let spans = found.chain(highlights).collect::<Vec<_>>();
+                    convert(&paragraph, &spans)
+                })
+                .into_view()
+            }
             Content::Image(item) => {
-                let Ok(data) = ctx.load(&item) else { return };
+                // still takes up an id, so ids agree with `search::find`
+                let Ok(data) = ctx.load(&item) else {
+                    id += 1;
+                    return;
+                };
                 let mime = item.mime();
                 let mut data_string = format!("data:{mime};base64,");
                 BASE64_STANDARD.encode_string(&data, &mut data_string);
@@ -34,28 +109,81 @@ pub fn chapter_to_html(
         };
         let tracker = tracker.clone();
         let view = html::div()
-            .id(id.to_string())
+            .id(para_id(page, id))
+            .attr("data-para", "")
             .child(view)
-            .on_mount(move |node| tracker.track(node, id, page));
+            .on_mount(move |node| tracker.track(node, page, id));
         out.push(view.into_view());
         id += 1;
     })
     .unwrap();
 
-    Some(out.into_iter().collect_view())
+    view! {
+        <div class="space-y-[var(--para-gap)]" data-page=page>
+            {out.into_iter().collect_view()}
+        </div>
+    }
+}
+
+/// Opening words of paragraph `para` of `page`.
+pub fn excerpt(book: &Epub, page: usize, para: usize) -> Option<String> {
+    const LEN: usize = 80;
+    let mut id = 0;
+    let mut excerpt = None;
+    book.traverse_chapter(page, |_, content, _| {
+        if id == para {
+            if let Content::Textual(text) = content {
+                let text = plain_text(&text).replace('\n', " ");
+                let mut short: String = text.chars().take(LEN).collect();
+                if text.chars().count() > LEN {
+                    short.push('…');
+                }
+                excerpt = Some(short);
+            }
+        }
+        id += 1;
+    })
+    .ok()?;
+    excerpt
+}
+
+/// The text of a paragraph, which character offsets in `Span`s refer to.
+pub fn plain_text(text: &Text<'_>) -> String {
+    text.style_chunks().map(|(slice, _)| slice).collect()
 }
 
-fn convert(text: &Text<'_>) -> leptos::View {
+// a paragraph's text, kept to render it again as its highlights change
+struct Paragraph {
+    chunks: Vec<(String, Style)>,
+    kind: TextKind,
+}
+
+impl Paragraph {
+    fn new(text: &Text<'_>) -> Self {
+        Self {
+            chunks: text
+                .style_chunks()
+                .map(|(slice, style)| (slice.to_owned(), style))
+                .collect(),
+            kind: text.kind(),
+        }
+    }
+}
+
+fn convert(text: &Paragraph, spans: &[Span]) -> leptos::View {
     let mut children = Vec::new();
-    for (slice, style) in text.style_chunks() {
+    let mut offset = 0;
+    for (slice, style) in &text.chunks {
         let mut views = Vec::new();
         for (i, chunk) in slice.split('\n').enumerate() {
             if i > 0 {
                 views.push(html::br().into_view());
+                offset += 1;
             }
             if !chunk.is_empty() {
-                views.push(chunk.to_owned().into_view());
+                views.extend(highlight(chunk, offset, spans));
             }
+            offset += chunk.chars().count();
         }
         let mut view = views.collect_view();
         if style.contains(Style::ITALIC) {
@@ -67,7 +195,7 @@ fn convert(text: &Text<'_>) -> leptos::View {
         children.push(view);
     }
 
-    match text.kind() {
+    match text.kind {
         TextKind::Header => html::h1()
             .attr(
                 "class",
@@ -82,3 +210,37 @@ fn convert(text: &Text<'_>) -> leptos::View {
             .into_view(),
     }
 }
+
+// split a chunk of text starting at character `offset` along span boundaries,
+// tagging each piece with its offset so selections can be mapped back to the text
+fn highlight(chunk: &str, offset: usize, spans: &[Span]) -> Vec<View> {
+    let len = chunk.chars().count();
+    let mut bounds = vec![0, len];
+    for span in spans {
+        for bound in [span.start, span.end] {
+            if bound > offset && bound < offset + len {
+                bounds.push(bound - offset);
+            }
+        }
+    }
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    let byte = |c: usize| chunk.char_indices().nth(c).map_or(chunk.len(), |(i, _)| i);
+    bounds
+        .windows(2)
+        .map(|w| {
+            let piece = chunk[byte(w[0])..byte(w[1])].to_owned();
+            let at = offset + w[0];
+            match spans.iter().find(|s| s.start <= at && at < s.end) {
+                Some(span) => html::mark()
+                    .classes(span.class)
+                    .attr("data-o", at)
+                    .attr("data-h", span.highlight.map(|h| h.to_string()))
+                    .child(piece)
+                    .into_view(),
+                None => html::span().attr("data-o", at).child(piece).into_view(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,116 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use leptos::wasm_bindgen::{JsCast as _, JsValue};
+use lepu::Epub;
+use serde::Serialize;
+use web_sys::{js_sys::Array, Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+
+use crate::{annotations::Highlight, book, position::Mark};
+
+#[derive(Serialize)]
+struct Export<'a> {
+    identifier: &'a str,
+    title: &'a str,
+    highlights: &'a [Highlight],
+    marks: &'a BTreeMap<char, Mark>,
+}
+
+/// Highlights and notes grouped by chapter, followed by marks.
+pub fn markdown(book: &Epub, highlights: &[Highlight], marks: &BTreeMap<char, Mark>) -> String {
+    let mut out = format!("# {}\n", book.title());
+
+    let mut chapter = None;
+    for h in highlights {
+        let name = book::chapter_name(book, h.page);
+        if chapter.as_ref() != Some(&name) {
+            let heading = name.as_deref().unwrap_or("Untitled");
+            out.push_str(&format!("\n## {heading}\n"));
+            chapter = Some(name);
+        }
+        out.push('\n');
+        for line in h.text.lines() {
+            out.push_str(&format!("> {line}\n"));
+        }
+        if !h.note.is_empty() {
+            out.push_str(&format!("\n{}\n", h.note));
+        }
+    }
+
+    if !marks.is_empty() {
+        out.push_str("\n## Marks\n\n");
+        for (name, mark) in marks {
+            let chapter = book::chapter_name(book, mark.page).unwrap_or_else(|| "Untitled".into());
+            let para = mark.para;
+            out.push_str(&format!("- `{name}`: {chapter}, paragraph {para}\n"));
+        }
+    }
+
+    out
+}
+
+pub fn json(book: &Epub, highlights: &[Highlight], marks: &BTreeMap<char, Mark>) -> String {
+    let export = Export {
+        identifier: book.identifier(),
+        title: book.title(),
+        highlights,
+        marks,
+    };
+    serde_json::to_string_pretty(&export).unwrap_or_default()
+}
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Markdown,
+    Json,
+}
+
+/// Download highlights, notes and marks of `book`.
+pub fn notes(book: &Epub, highlights: &[Highlight], marks: &BTreeMap<char, Mark>, format: Format) {
+    let name = file_name(book.title());
+    let _ = match format {
+        Format::Markdown => download(
+            &format!("{name}-notes.md"),
+            "text/markdown",
+            &markdown(book, highlights, marks),
+        ),
+        Format::Json => download(
+            &format!("{name}-notes.json"),
+            "application/json",
+            &json(book, highlights, marks),
+        ),
+    };
+}
+
+fn file_name(title: &str) -> String {
+    let name: String = title
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    let name = name.trim_matches('-');
+    if name.is_empty() {
+        "book".to_owned()
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Have the browser save `contents` as a file.
+pub fn download(name: &str, mime: &str, contents: &str) -> Result<(), JsValue> {
+    let parts = Array::of1(&JsValue::from_str(contents));
+    let blob = Blob::new_with_str_sequence_and_options(&parts, BlobPropertyBag::new().type_(mime))?;
+    let url = Url::create_object_url_with_blob(&blob)?;
+    let a = leptos::document()
+        .create_element("a")?
+        .unchecked_into::<HtmlAnchorElement>();
+    a.set_href(&url);
+    a.set_download(name);
+    a.click();
+    // the download may not have started yet
+    leptos::set_timeout(
+        move || {
+            let _ = Url::revoke_object_url(&url);
+        },
+        Duration::from_secs(10),
+    );
+    Ok(())
+}
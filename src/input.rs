@@ -1,38 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    export::Format,
+    keymap::{format_sequence, Command, Keymap, Trie},
+    theme::Theme,
+};
+
 pub struct Handler {
     state: State,
+    keymap: Keymap,
+    trie: Trie,
+    // keys typed so far of a multi-key sequence
+    keys: Vec<String>,
+    // count typed before a command, as in `5<Right>`
+    count: Option<usize>,
 }
 
 pub enum Action {
-    NextPage,
-    PreviousPage,
+    NextPage(usize),
+    PreviousPage(usize),
+    GoToPage(usize),
+    /// Go to a fraction of the way through the book.
+    GoToPercent(f64),
+    Top,
+    Bottom,
+    NextParagraph(usize),
+    PreviousParagraph(usize),
+    HalfPageDown(usize),
+    HalfPageUp(usize),
     Leave,
     SetMark(char),
     FollowMark(char),
+    Search {
+        query: String,
+        backwards: bool,
+    },
+    NextMatch,
+    PreviousMatch,
+    JumpBack,
+    JumpForward,
+    Help,
+    CommandLine,
+    SetTheme(Theme),
+    Export(Format),
+    ToggleNavBar,
+}
+
+/// What tapping a zone of the page does on touch screens.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TapAction {
+    Nothing,
+    PreviousPage,
+    NextPage,
+    ToggleNavBar,
+}
+
+impl TapAction {
+    pub const ALL: [TapAction; 4] = [
+        TapAction::Nothing,
+        TapAction::PreviousPage,
+        TapAction::NextPage,
+        TapAction::ToggleNavBar,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TapAction::Nothing => "nothing",
+            TapAction::PreviousPage => "previous-page",
+            TapAction::NextPage => "next-page",
+            TapAction::ToggleNavBar => "toggle-nav-bar",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            TapAction::Nothing => "nothing",
+            TapAction::PreviousPage => "previous chapter",
+            TapAction::NextPage => "next chapter",
+            TapAction::ToggleNavBar => "show or hide the navigation bar",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<TapAction> {
+        TapAction::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    pub fn action(self) -> Option<Action> {
+        match self {
+            TapAction::Nothing => None,
+            TapAction::PreviousPage => Some(Action::PreviousPage(1)),
+            TapAction::NextPage => Some(Action::NextPage(1)),
+            TapAction::ToggleNavBar => Some(Action::ToggleNavBar),
+        }
+    }
 }
 
 impl Handler {
-    pub fn new() -> Self {
+    pub fn new(keymap: &Keymap) -> Self {
         Self {
             state: State::Normal,
+            keymap: keymap.clone(),
+            trie: keymap.trie(),
+            keys: Vec::new(),
+            count: None,
         }
     }
 
     pub fn handle(&mut self, input: &str) -> Option<Action> {
-        match self.state {
-            State::Normal => match input {
-                "ArrowRight" => Some(Action::NextPage),
-                "ArrowLeft" => Some(Action::PreviousPage),
-                "m" => {
-                    self.state = State::SetMark;
-                    None
-                }
-                "g" => {
-                    self.state = State::FollowMark;
-                    None
-                }
-                "Escape" => Some(Action::Leave),
-                _ => None,
-            },
+        match &mut self.state {
+            State::Normal => self.sequence(input),
             State::SetMark => {
                 self.state = State::Normal;
                 get_char(input).map(Action::SetMark)
@@ -41,12 +118,171 @@ impl Handler {
                 self.state = State::Normal;
                 get_char(input).map(Action::FollowMark)
             }
+            State::Search { query, backwards } => match input {
+                "Enter" => {
+                    let query = std::mem::take(query);
+                    let backwards = *backwards;
+                    self.state = State::Normal;
+                    (!query.is_empty()).then_some(Action::Search { query, backwards })
+                }
+                "Escape" => {
+                    self.state = State::Normal;
+                    None
+                }
+                "Backspace" => {
+                    if query.pop().is_none() {
+                        self.state = State::Normal;
+                    }
+                    None
+                }
+                _ => {
+                    if input.chars().count() == 1 {
+                        query.push_str(input);
+                    }
+                    None
+                }
+            },
+        }
+    }
+
+    fn sequence(&mut self, input: &str) -> Option<Action> {
+        if input == "Escape" && (!self.keys.is_empty() || self.count.is_some()) {
+            self.keys.clear();
+            self.count = None;
+            return None;
+        }
+
+        // a count is only read before the first key of a sequence, and `0`
+        // can't start one
+        if let Some(digit) = input.parse::<usize>().ok().filter(|_| self.keys.is_empty()) {
+            if digit > 0 || self.count.is_some() {
+                let count = self.count.unwrap_or(0);
+                self.count = Some(count.saturating_mul(10).saturating_add(digit));
+                return None;
+            }
         }
+
+        self.keys.push(input.to_owned());
+        let Some(node) = self.trie.get(&self.keys) else {
+            // the sequence doesn't continue, so fall back to what the keys
+            // before this one are bound to, e.g. `g` followed by a mark name
+            self.keys.pop();
+            let command = self.trie.get(&self.keys).and_then(|node| node.command);
+            let retry = !self.keys.is_empty();
+            self.keys.clear();
+            return match command {
+                Some(command) => match self.run(command) {
+                    Some(action) => Some(action),
+                    None => self.handle(input),
+                },
+                None if retry => self.sequence(input),
+                None => {
+                    self.count = None;
+                    None
+                }
+            };
+        };
+
+        // wait for the rest of a longer sequence
+        if !node.children.is_empty() {
+            return None;
+        }
+        let command = node.command;
+        self.keys.clear();
+        command.and_then(|command| self.run(command))
+    }
+
+    fn run(&mut self, command: Command) -> Option<Action> {
+        let count = self.count.take();
+        match command {
+            Command::NextPage => Some(Action::NextPage(count.unwrap_or(1))),
+            Command::PreviousPage => Some(Action::PreviousPage(count.unwrap_or(1))),
+            // counts are 1-based, like the chapters shown to the reader
+            Command::GoToPage => Some(count.map_or(Action::Bottom, |c| Action::GoToPage(c - 1))),
+            Command::Top => Some(count.map_or(Action::Top, |c| Action::GoToPage(c - 1))),
+            Command::NextParagraph => Some(Action::NextParagraph(count.unwrap_or(1))),
+            Command::PreviousParagraph => Some(Action::PreviousParagraph(count.unwrap_or(1))),
+            Command::HalfPageDown => Some(Action::HalfPageDown(count.unwrap_or(1))),
+            Command::HalfPageUp => Some(Action::HalfPageUp(count.unwrap_or(1))),
+            Command::Leave => Some(Action::Leave),
+            Command::NextMatch => Some(Action::NextMatch),
+            Command::PreviousMatch => Some(Action::PreviousMatch),
+            Command::JumpBack => Some(Action::JumpBack),
+            Command::JumpForward => Some(Action::JumpForward),
+            Command::Help => Some(Action::Help),
+            Command::CommandLine => Some(Action::CommandLine),
+            Command::SetMark => {
+                self.state = State::SetMark;
+                None
+            }
+            Command::FollowMark => {
+                self.state = State::FollowMark;
+                None
+            }
+            Command::Search | Command::SearchBackward => {
+                self.state = State::Search {
+                    query: String::new(),
+                    backwards: command == Command::SearchBackward,
+                };
+                None
+            }
+        }
+    }
+
+    /// Description of a partially entered command, if any.
+    pub fn pending(&self) -> Option<String> {
+        match &self.state {
+            State::Search { query, backwards } => {
+                let prefix = if *backwards { '?' } else { '/' };
+                Some(format!("{prefix}{query}"))
+            }
+            State::SetMark => Some("set mark…".to_owned()),
+            State::FollowMark => Some("go to mark…".to_owned()),
+            State::Normal if !self.keys.is_empty() || self.count.is_some() => {
+                let count = self.count.map(|c| c.to_string()).unwrap_or_default();
+                Some(format!("{count}{}", format_sequence(&self.keys)))
+            }
+            State::Normal => None,
+        }
+    }
+
+    /// Bindings the handler was created with.
+    pub fn keymap(&self) -> &Keymap {
+        &self.keymap
+    }
+}
+
+/// Modifier keys held along with a key.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+/// Name `Handler::handle` knows a key by, prefixed with `C-`, `A-` and `M-`,
+/// in that order, while ctrl, alt and meta are held.
+pub fn key_name(key: &str, modifiers: Modifiers) -> String {
+    let Modifiers { ctrl, alt, meta } = modifiers;
+    if !(ctrl || alt || meta) {
+        return key.to_owned();
+    }
+    let mut name = String::new();
+    for (held, prefix) in [(ctrl, "C-"), (alt, "A-"), (meta, "M-")] {
+        if held {
+            name.push_str(prefix);
+        }
+    }
+    // shift doesn't change combinations
+    match key.chars().count() {
+        1 => name.push_str(&key.to_lowercase()),
+        _ => name.push_str(key),
     }
+    name
 }
 
 fn get_char(input: &str) -> Option<char> {
-    if input.len() == 1 && matches!(input.chars().next(), Some('a'..='z')) {
+    if input.len() == 1 && matches!(input.chars().next(), Some('a'..='z' | 'A'..='Z')) {
         return Some(input.chars().next().unwrap());
     }
     None
@@ -56,4 +292,5 @@ enum State {
     Normal,
     SetMark,
     FollowMark,
+    Search { query: String, backwards: bool },
 }
@@ -0,0 +1,468 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::input::{key_name, Modifiers};
+
+/// Something a key sequence can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Command {
+    NextPage,
+    PreviousPage,
+    GoToPage,
+    Top,
+    NextParagraph,
+    PreviousParagraph,
+    HalfPageDown,
+    HalfPageUp,
+    Leave,
+    SetMark,
+    FollowMark,
+    Search,
+    SearchBackward,
+    NextMatch,
+    PreviousMatch,
+    JumpBack,
+    JumpForward,
+    Help,
+    CommandLine,
+}
+
+impl Command {
+    pub const ALL: [Command; 19] = [
+        Command::NextPage,
+        Command::PreviousPage,
+        Command::GoToPage,
+        Command::Top,
+        Command::NextParagraph,
+        Command::PreviousParagraph,
+        Command::HalfPageDown,
+        Command::HalfPageUp,
+        Command::Leave,
+        Command::SetMark,
+        Command::FollowMark,
+        Command::Search,
+        Command::SearchBackward,
+        Command::NextMatch,
+        Command::PreviousMatch,
+        Command::JumpBack,
+        Command::JumpForward,
+        Command::Help,
+        Command::CommandLine,
+    ];
+
+    /// Identifier used when storing bindings.
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::NextPage => "next-page",
+            Command::PreviousPage => "previous-page",
+            Command::GoToPage => "go-to-page",
+            Command::Top => "top",
+            Command::NextParagraph => "next-paragraph",
+            Command::PreviousParagraph => "previous-paragraph",
+            Command::HalfPageDown => "half-page-down",
+            Command::HalfPageUp => "half-page-up",
+            Command::Leave => "leave",
+            Command::SetMark => "set-mark",
+            Command::FollowMark => "follow-mark",
+            Command::Search => "search",
+            Command::SearchBackward => "search-backward",
+            Command::NextMatch => "next-match",
+            Command::PreviousMatch => "previous-match",
+            Command::JumpBack => "jump-back",
+            Command::JumpForward => "jump-forward",
+            Command::Help => "help",
+            Command::CommandLine => "command-line",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Command::NextPage => "Next chapter",
+            Command::PreviousPage => "Previous chapter",
+            Command::GoToPage => "Bottom of chapter, or chapter (count)",
+            Command::Top => "Top of chapter, or chapter (count)",
+            Command::NextParagraph => "Next paragraph",
+            Command::PreviousParagraph => "Previous paragraph",
+            Command::HalfPageDown => "Scroll half a screen down",
+            Command::HalfPageUp => "Scroll half a screen up",
+            Command::Leave => "Back to table of contents",
+            Command::SetMark => "Set mark (followed by a letter)",
+            Command::FollowMark => "Go to mark (followed by a letter)",
+            Command::Search => "Search forwards",
+            Command::SearchBackward => "Search backwards",
+            Command::NextMatch => "Next search match",
+            Command::PreviousMatch => "Previous search match",
+            Command::JumpBack => "Jump back",
+            Command::JumpForward => "Jump forward",
+            Command::Help => "Show these keys",
+            Command::CommandLine => "Enter a command, such as :chapter 3",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Command> {
+        Command::ALL.into_iter().find(|c| c.name() == name)
+    }
+}
+
+// named keys, as written in sequences and as reported by the browser
+const NAMED: &[(&str, &str)] = &[
+    ("Right", "ArrowRight"),
+    ("Left", "ArrowLeft"),
+    ("Up", "ArrowUp"),
+    ("Down", "ArrowDown"),
+    ("Esc", "Escape"),
+    ("CR", "Enter"),
+    ("BS", "Backspace"),
+    ("Tab", "Tab"),
+    ("Space", " "),
+    ("Home", "Home"),
+    ("End", "End"),
+    ("PageUp", "PageUp"),
+    ("PageDown", "PageDown"),
+    ("lt", "<"),
+];
+
+/// Parse a key sequence such as `gg`, `]c`, `<C-o>` or `<A-Right>` into the key names
+/// `input::key_name` produces.
+pub fn parse_sequence(s: &str) -> Option<Vec<String>> {
+    let mut keys = Vec::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '<' {
+            keys.push(c.to_string());
+            continue;
+        }
+        let rest = chars.as_str();
+        let end = rest.find('>')?;
+        let inner = &rest[..end];
+        chars = rest[end + 1..].chars();
+        keys.push(parse_key(inner)?);
+    }
+    (!keys.is_empty()).then_some(keys)
+}
+
+fn parse_key(inner: &str) -> Option<String> {
+    // modifiers, in any order
+    let mut modifiers = Modifiers::default();
+    let mut key = inner;
+    while key.chars().count() > 1 {
+        let held = match key.get(..2) {
+            Some("C-") => &mut modifiers.ctrl,
+            Some("A-") => &mut modifiers.alt,
+            Some("M-") => &mut modifiers.meta,
+            _ => break,
+        };
+        *held = true;
+        key = &key[2..];
+    }
+    let key = match key.chars().count() {
+        1 => key.to_owned(),
+        _ => NAMED
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, key)| (*key).to_owned())?,
+    };
+    Some(key_name(&key, modifiers))
+}
+
+/// Inverse of `parse_sequence`.
+pub fn format_sequence(keys: &[String]) -> String {
+    keys.iter().map(|key| format_key(key)).collect()
+}
+
+fn format_key(key: &str) -> String {
+    let mut modifiers = String::new();
+    let mut key = key;
+    while key.chars().count() > 2 && matches!(key.get(..2), Some("C-" | "A-" | "M-")) {
+        modifiers.push_str(&key[..2]);
+        key = &key[2..];
+    }
+    let named = NAMED.iter().find(|(_, k)| *k == key).map(|(name, _)| *name);
+    match (modifiers.is_empty(), named) {
+        (true, None) => key.to_owned(),
+        (true, Some(name)) => format!("<{name}>"),
+        (false, name) => format!("<{modifiers}{}>", name.unwrap_or(key)),
+    }
+}
+
+/// Key sequences and the commands they are bound to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap {
+    pub bindings: Vec<(String, Command)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = [
+            ("<Right>", Command::NextPage),
+            ("<Left>", Command::PreviousPage),
+            ("G", Command::GoToPage),
+            ("gg", Command::Top),
+            ("j", Command::NextParagraph),
+            ("k", Command::PreviousParagraph),
+            ("d", Command::HalfPageDown),
+            ("u", Command::HalfPageUp),
+            ("<Esc>", Command::Leave),
+            ("m", Command::SetMark),
+            ("g", Command::FollowMark),
+            // `g` followed by `g` goes to the top instead
+            ("'", Command::FollowMark),
+            ("/", Command::Search),
+            // `?` opens the help, so searching backwards moves to `g/`
+            ("g/", Command::SearchBackward),
+            ("n", Command::NextMatch),
+            ("N", Command::PreviousMatch),
+            ("<C-o>", Command::JumpBack),
+            ("<C-i>", Command::JumpForward),
+            ("?", Command::Help),
+            (":", Command::CommandLine),
+        ];
+        Self {
+            bindings: bindings
+                .into_iter()
+                .map(|(seq, command)| (seq.to_owned(), command))
+                .collect(),
+        }
+    }
+}
+
+// every command with its sequences, none for those that aren't bound
+impl Serialize for Keymap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            Command::ALL
+                .into_iter()
+                .map(|c| (c.name(), self.sequences(c).collect::<Vec<_>>())),
+        )
+    }
+}
+
+// commands missing, e.g. ones added after the bindings were saved, or with
+// anything but a list of sequences keep their default bindings, and
+// sequences that don't parse are dropped
+impl<'de> Deserialize<'de> for Keymap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let saved = BTreeMap::<String, Value>::deserialize(deserializer)?;
+        let default = Keymap::default();
+        let bindings = Command::ALL
+            .into_iter()
+            .flat_map(|command| {
+                let sequences = match saved.get(command.name()).map(Vec::<String>::deserialize) {
+                    Some(Ok(sequences)) => sequences
+                        .into_iter()
+                        .filter(|seq| parse_sequence(seq).is_some())
+                        .collect::<Vec<_>>(),
+                    _ => default.sequences(command).map(str::to_owned).collect(),
+                };
+                sequences.into_iter().map(move |seq| (seq, command))
+            })
+            .collect();
+        Ok(Keymap { bindings })
+    }
+}
+
+impl Keymap {
+    /// Sequences bound to `command`.
+    pub fn sequences(&self, command: Command) -> impl Iterator<Item = &str> {
+        self.bindings
+            .iter()
+            .filter(move |(_, c)| *c == command)
+            .map(|(seq, _)| seq.as_str())
+    }
+
+    /// Replace the sequences bound to `command`.
+    pub fn rebind(&mut self, command: Command, sequences: Vec<String>) {
+        self.bindings.retain(|(_, c)| *c != command);
+        self.bindings
+            .extend(sequences.into_iter().map(|seq| (seq, command)));
+    }
+
+    /// Read bindings stored before `Config::to_json`: one `{sequence}
+    /// {command}` per line, with an empty sequence for commands that aren't
+    /// bound at all.
+    pub fn from_legacy(s: &str) -> Option<Self> {
+        let lines = s
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let (seq, command) = line.rsplit_once(' ')?;
+                if !seq.is_empty() {
+                    parse_sequence(seq)?;
+                }
+                Some((seq.to_owned(), Command::parse(command)?))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut keymap = Keymap::default();
+        keymap
+            .bindings
+            .retain(|(_, command)| !lines.iter().any(|(_, c)| c == command));
+        keymap
+            .bindings
+            .extend(lines.into_iter().filter(|(seq, _)| !seq.is_empty()));
+        Some(keymap)
+    }
+
+    pub fn trie(&self) -> Trie {
+        let mut root = Trie::default();
+        for (seq, command) in &self.bindings {
+            let Some(keys) = parse_sequence(seq) else {
+                continue;
+            };
+            let mut node = &mut root;
+            for key in keys {
+                node = node.children.entry(key).or_default();
+            }
+            node.command = Some(*command);
+        }
+        root
+    }
+}
+
+/// Bindings arranged by key, so sequences can be matched a key at a time.
+#[derive(Default)]
+pub struct Trie {
+    pub command: Option<Command>,
+    pub children: BTreeMap<String, Trie>,
+}
+
+impl Trie {
+    pub fn get(&self, keys: &[String]) -> Option<&Trie> {
+        keys.iter()
+            .try_fold(self, |node, key| node.children.get(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{Action, Handler};
+
+    fn keys(keys: &[&str]) -> Option<Vec<String>> {
+        Some(keys.iter().map(|&k| k.to_owned()).collect())
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_sequence("gg"), keys(&["g", "g"]));
+        assert_eq!(parse_sequence("]c"), keys(&["]", "c"]));
+        assert_eq!(parse_sequence("<C-o>"), keys(&["C-o"]));
+        assert_eq!(parse_sequence("<C-O>"), keys(&["C-o"]));
+        assert_eq!(parse_sequence("<right>"), keys(&["ArrowRight"]));
+        assert_eq!(parse_sequence("<A-Right>"), keys(&["A-ArrowRight"]));
+        assert_eq!(parse_sequence("<M-x>"), keys(&["M-x"]));
+        // modifiers are put in one order
+        assert_eq!(parse_sequence("<M-A-C-x>"), keys(&["C-A-M-x"]));
+        assert_eq!(parse_sequence("<C-->"), keys(&["C--"]));
+        assert_eq!(parse_sequence("<lt>g"), keys(&["<", "g"]));
+        assert_eq!(parse_sequence("<Space><CR>"), keys(&[" ", "Enter"]));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for invalid in ["", "<", "<C-o", "<>", "<C->", "<Nope>", "<X-a>", "g<Esc"] {
+            assert_eq!(parse_sequence(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn format_round_trip() {
+        for seq in [
+            "gg",
+            "]c",
+            "<C-o>",
+            "<Right>",
+            "<Esc>",
+            "<lt>",
+            "<C-lt>",
+            "<A-x>",
+            "<M-Right>",
+            "<C-A-M-x>",
+            "g/",
+            "<Space>",
+        ] {
+            let keys = parse_sequence(seq).unwrap();
+            assert_eq!(format_sequence(&keys), seq);
+        }
+        // every default binding survives being shown and read back
+        for (seq, _) in Keymap::default().bindings {
+            let keys = parse_sequence(&seq).unwrap();
+            assert_eq!(parse_sequence(&format_sequence(&keys)), Some(keys));
+        }
+    }
+
+    #[test]
+    fn key_names_match_parsed_sequences() {
+        let key = |key, ctrl, alt, meta| key_name(key, Modifiers { ctrl, alt, meta });
+        assert_eq!(
+            parse_sequence("<C-o>"),
+            keys(&[key("O", true, false, false).as_str()])
+        );
+        assert_eq!(
+            parse_sequence("<A-j>"),
+            keys(&[key("j", false, true, false).as_str()])
+        );
+        assert_eq!(
+            parse_sequence("<C-M-Left>"),
+            keys(&[key("ArrowLeft", true, false, true).as_str()])
+        );
+        assert_eq!(
+            parse_sequence("G"),
+            keys(&[key("G", false, false, false).as_str()])
+        );
+    }
+
+    #[test]
+    fn from_legacy() {
+        let keymap = Keymap::from_legacy("l next-page\n<C-n> next-page\n leave\n\n").unwrap();
+        assert_eq!(
+            keymap.sequences(Command::NextPage).collect::<Vec<_>>(),
+            ["l", "<C-n>"]
+        );
+        assert_eq!(keymap.sequences(Command::Leave).count(), 0);
+        // what isn't mentioned keeps its default
+        assert_eq!(
+            keymap.sequences(Command::JumpBack).collect::<Vec<_>>(),
+            ["<C-o>"]
+        );
+
+        assert_eq!(Keymap::from_legacy("<Nope> next-page"), None);
+        assert_eq!(Keymap::from_legacy("l nowhere"), None);
+        assert_eq!(Keymap::from_legacy("next-page"), None);
+    }
+
+    #[test]
+    fn trie_prefixes() {
+        let trie = Keymap::from_legacy("g follow-mark\ngn next-page\n")
+            .unwrap()
+            .trie();
+        let g = trie.get(&keys(&["g"]).unwrap()).unwrap();
+        assert_eq!(g.command, Some(Command::FollowMark));
+        assert_eq!(g.children["n"].command, Some(Command::NextPage));
+        assert!(trie.get(&keys(&["g", "n", "n"]).unwrap()).is_none());
+        assert!(trie.get(&keys(&["x"]).unwrap()).is_none());
+    }
+
+    #[test]
+    fn handler_resolves_prefixes() {
+        let keymap = Keymap::from_legacy("g follow-mark\ngn next-page\n").unwrap();
+        let mut handler = Handler::new(&keymap);
+        // `g` waits to see whether `gn` follows
+        assert!(handler.handle("g").is_none());
+        assert!(matches!(handler.handle("n"), Some(Action::NextPage(1))));
+        // or runs on its own, here following mark `a`
+        assert!(handler.handle("g").is_none());
+        assert!(matches!(handler.handle("a"), Some(Action::FollowMark('a'))));
+        assert!(handler.handle("3").is_none());
+        assert!(handler.handle("g").is_none());
+        assert!(matches!(handler.handle("n"), Some(Action::NextPage(3))));
+        assert!(handler.handle("3").is_none());
+        assert!(matches!(handler.handle("G"), Some(Action::GoToPage(2))));
+        assert!(matches!(handler.handle("G"), Some(Action::Bottom)));
+        // `gg` is bound by default next to `gn`
+        assert!(handler.handle("g").is_none());
+        assert!(matches!(handler.handle("g"), Some(Action::Top)));
+    }
+}
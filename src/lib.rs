@@ -1,3 +1,5 @@
+mod annotations;
+
 mod app;
 pub use app::App;
 
@@ -11,10 +13,32 @@ pub use config::Config;
 
 mod content;
 
+mod export;
+
 mod input;
 
+mod keymap;
+
+mod library;
+
 mod nav_state;
 pub use nav_state::{set_nav_state, NavState};
 
+mod opf;
+mod overrides;
+
+mod pages;
+
+mod palette;
+
 mod position;
 pub use position::Marks;
+
+mod search;
+
+mod storage;
+pub use storage::init as init_storage;
+
+mod theme;
+
+mod typography;
@@ -0,0 +1,241 @@
+use base64::prelude::*;
+use leptos::*;
+use lepu::{Content, Epub};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{closure::Closure, JsCast as _, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    js_sys::{Array, Date, Promise, Uint8Array},
+    IdbDatabase, IdbObjectStore, IdbRequest, IdbTransactionMode,
+};
+
+// indexeddb usage
+// "wepu" database, version 3
+// "books" object store => raw epub bytes (Uint8Array), keyed by book identifier
+// "meta" object store => json encoded `Entry`, keyed by book identifier
+// "settings" object store => strings, keyed like local storage (only used
+//                            without local storage, see `storage::Indexed`)
+
+const DB_NAME: &str = "wepu";
+const DB_VERSION: u32 = 3;
+const BOOKS: &str = "books";
+const META: &str = "meta";
+const SETTINGS: &str = "settings";
+
+/// Shelf metadata for a stored book, kept separately from its data so the
+/// library can be listed without parsing every book.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: String,
+    pub title: String,
+    pub author: String,
+    /// Cover image as a data url.
+    pub cover: Option<String>,
+    pub document_count: usize,
+    /// Milliseconds since the epoch.
+    pub added: f64,
+    /// Milliseconds since the epoch.
+    pub opened: f64,
+}
+
+impl Entry {
+    pub fn new(epub: &Epub) -> Self {
+        let now = Date::now();
+        Self {
+            id: epub.identifier().to_owned(),
+            title: epub.title().to_owned(),
+            author: epub.author().to_owned(),
+            cover: cover(epub),
+            document_count: epub.document_count(),
+            added: now,
+            opened: now,
+        }
+    }
+}
+
+// first image in the opening documents of the book, usually the cover
+fn cover(epub: &Epub) -> Option<String> {
+    let mut cover = None;
+    for page in 0..epub.document_count().min(3) {
+        let _ = epub.traverse_chapter(page, |ctx, content, _| {
+            if cover.is_some() {
+                return;
+            }
+            let Content::Image(item) = content else {
+                return;
+            };
+            let Ok(data) = ctx.load(&item) else { return };
+            let mime = item.mime();
+            let mut data_string = format!("data:{mime};base64,");
+            BASE64_STANDARD.encode_string(&data, &mut data_string);
+            cover = Some(data_string);
+        });
+        if cover.is_some() {
+            break;
+        }
+    }
+    cover
+}
+
+// resolves once the request has finished, yielding its result
+async fn wait(req: &IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        req.set_onsuccess(Some(&resolve));
+        req.set_onerror(Some(&reject));
+    });
+    JsFuture::from(promise).await?;
+    req.result()
+}
+
+async fn open() -> Result<IdbDatabase, JsValue> {
+    let factory = window()
+        .indexed_db()?
+        .ok_or_else(|| JsValue::from_str("indexeddb unavailable"))?;
+    let req = factory.open_with_u32(DB_NAME, DB_VERSION)?;
+
+    let upgrade_req = req.clone();
+    let on_upgrade = Closure::once_into_js(move |_: web_sys::Event| {
+        let Ok(db) = upgrade_req.result() else { return };
+        let db = db.unchecked_into::<IdbDatabase>();
+        for name in [BOOKS, META, SETTINGS] {
+            if !db.object_store_names().contains(name) {
+                let _ = db.create_object_store(name);
+            }
+        }
+    });
+    req.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+
+    Ok(wait(&req).await?.unchecked_into())
+}
+
+async fn request(
+    store: &str,
+    mode: IdbTransactionMode,
+    f: impl FnOnce(&IdbObjectStore) -> Result<IdbRequest, JsValue>,
+) -> Result<JsValue, JsValue> {
+    let db = open().await?;
+    let tx = db.transaction_with_str_and_mode(store, mode)?;
+    let req = f(&tx.object_store(store)?)?;
+    let res = wait(&req).await;
+    db.close();
+    res
+}
+
+/// Store a book's raw EPUB data, replacing any existing copy.
+pub async fn put(id: &str, data: &Uint8Array) -> Result<(), JsValue> {
+    let key = JsValue::from_str(id);
+    request(BOOKS, IdbTransactionMode::Readwrite, |store| {
+        store.put_with_key(data, &key)
+    })
+    .await
+    .map(|_| ())
+}
+
+/// Fetch a book's raw EPUB data.
+pub async fn get(id: &str) -> Option<Vec<u8>> {
+    let key = JsValue::from_str(id);
+    let res = request(BOOKS, IdbTransactionMode::Readonly, |store| store.get(&key))
+        .await
+        .ok()?;
+    if res.is_undefined() {
+        return None;
+    }
+    Some(res.unchecked_into::<Uint8Array>().to_vec())
+}
+
+/// Remove a book and its shelf entry.
+pub async fn remove(id: &str) -> Result<(), JsValue> {
+    let key = JsValue::from_str(id);
+    request(META, IdbTransactionMode::Readwrite, |store| {
+        store.delete(&key)
+    })
+    .await?;
+    request(BOOKS, IdbTransactionMode::Readwrite, |store| {
+        store.delete(&key)
+    })
+    .await
+    .map(|_| ())
+}
+
+pub async fn put_entry(entry: &Entry) -> Result<(), JsValue> {
+    let key = JsValue::from_str(&entry.id);
+    let value = serde_json::to_string(entry).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    request(META, IdbTransactionMode::Readwrite, |store| {
+        store.put_with_key(&JsValue::from_str(&value), &key)
+    })
+    .await
+    .map(|_| ())
+}
+
+pub async fn entry(id: &str) -> Option<Entry> {
+    let key = JsValue::from_str(id);
+    let res = request(META, IdbTransactionMode::Readonly, |store| store.get(&key))
+        .await
+        .ok()?;
+    serde_json::from_str(&res.as_string()?).ok()
+}
+
+/// Shelf entries for every stored book, in no particular order.
+pub async fn entries() -> Vec<Entry> {
+    let Ok(values) = request(META, IdbTransactionMode::Readonly, |store| store.get_all()).await
+    else {
+        return Vec::new();
+    };
+    values
+        .unchecked_into::<Array>()
+        .iter()
+        .filter_map(|value| serde_json::from_str(&value.as_string()?).ok())
+        .collect()
+}
+
+/// Record that a book was just opened.
+pub async fn touch(id: &str) {
+    if let Some(mut entry) = entry(id).await {
+        entry.opened = Date::now();
+        let _ = put_entry(&entry).await;
+    }
+}
+
+pub async fn clear() -> Result<(), JsValue> {
+    request(META, IdbTransactionMode::Readwrite, |store| store.clear()).await?;
+    request(BOOKS, IdbTransactionMode::Readwrite, |store| store.clear())
+        .await
+        .map(|_| ())
+}
+
+/// Every key and value in the settings store.
+pub async fn settings() -> Result<Vec<(String, String)>, JsValue> {
+    let keys = request(SETTINGS, IdbTransactionMode::Readonly, |store| {
+        store.get_all_keys()
+    })
+    .await?;
+    let values = request(SETTINGS, IdbTransactionMode::Readonly, |store| {
+        store.get_all()
+    })
+    .await?;
+    // both in key order
+    Ok(keys
+        .unchecked_into::<Array>()
+        .iter()
+        .zip(values.unchecked_into::<Array>().iter())
+        .filter_map(|(key, value)| Some((key.as_string()?, value.as_string()?)))
+        .collect())
+}
+
+pub async fn put_setting(key: &str, value: &str) -> Result<(), JsValue> {
+    let key = JsValue::from_str(key);
+    request(SETTINGS, IdbTransactionMode::Readwrite, |store| {
+        store.put_with_key(&JsValue::from_str(value), &key)
+    })
+    .await
+    .map(|_| ())
+}
+
+pub async fn remove_setting(key: &str) -> Result<(), JsValue> {
+    let key = JsValue::from_str(key);
+    request(SETTINGS, IdbTransactionMode::Readwrite, |store| {
+        store.delete(&key)
+    })
+    .await
+    .map(|_| ())
+}
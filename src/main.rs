@@ -1,7 +1,11 @@
-use leptos::mount_to_body;
-use wepu::App;
+use leptos::{mount_to_body, spawn_local};
+use wepu::{init_storage, App};
 
 fn main() {
     console_error_panic_hook::set_once();
-    mount_to_body(App);
+    // picking where settings live may have to wait on indexeddb
+    spawn_local(async {
+        init_storage().await;
+        mount_to_body(App);
+    });
 }
@@ -5,13 +5,33 @@ pub enum NavState {
     Upload,
     Read,
     Toc,
+    Search,
+    Bookmarks,
+    Library,
     Settings,
 }
 
+// whether the reader hid the nav bar, which only applies while reading
+#[derive(Clone, Copy)]
+struct NavBarHidden(RwSignal<bool>);
+
 pub fn init() {
     let (nav_state, set_nav_state) = create_signal(NavState::Upload);
     provide_context(nav_state);
     provide_context(set_nav_state);
+    provide_context(NavBarHidden(create_rw_signal(false)));
+}
+
+pub fn nav_bar_hidden() -> bool {
+    let nav_state = expect_context::<ReadSignal<NavState>>();
+    let hidden = expect_context::<NavBarHidden>().0;
+    hidden.get() && nav_state.get() == NavState::Read
+}
+
+pub fn toggle_nav_bar() {
+    expect_context::<NavBarHidden>()
+        .0
+        .update(|hidden| *hidden = !*hidden);
 }
 
 pub fn set_nav_state(state: NavState) {
@@ -0,0 +1,362 @@
+// What the reader needs from a book's OPF package document beyond what lepu
+// parses: the direction its pages progress in. An EPUB is a zip archive whose
+// `META-INF/container.xml` names the package document, read here directly.
+
+use leptos::wasm_bindgen::{JsCast as _, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    js_sys::{Array, Function, Reflect, Uint8Array},
+    Blob, ReadableWritablePair, Response,
+};
+
+/// Whether the spine's `page-progression-direction` of `epub` is `rtl`,
+/// `false` if its package can't be read.
+pub async fn right_to_left(epub: &[u8]) -> bool {
+    let Some(package) = package(epub).await else {
+        return false;
+    };
+    attribute(&package, "spine", "page-progression-direction") == Some("rtl")
+}
+
+// the package document, wherever the container says it is
+async fn package(epub: &[u8]) -> Option<String> {
+    let container = entry(epub, "META-INF/container.xml")?.text().await?;
+    let path = attribute(&container, "rootfile", "full-path")?;
+    entry(epub, path)?.text().await
+}
+
+// a file in the archive, as stored
+#[derive(Debug, PartialEq)]
+struct Entry<'a> {
+    deflated: bool,
+    data: &'a [u8],
+}
+
+impl Entry<'_> {
+    async fn text(&self) -> Option<String> {
+        match self.deflated {
+            true => inflate(self.data).await,
+            false => String::from_utf8(self.data.to_vec()).ok(),
+        }
+    }
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<usize> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?) as usize)
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<usize> {
+    usize::try_from(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?)).ok()
+}
+
+fn u64_at(data: &[u8], at: usize) -> Option<usize> {
+    usize::try_from(u64::from_le_bytes(data.get(at..at + 8)?.try_into().ok()?)).ok()
+}
+
+// offset and number of entries of the central directory, taken from the zip64
+// end of central directory record when they don't fit the plain one
+fn directory(zip: &[u8]) -> Option<(usize, usize)> {
+    // the end of central directory record, followed by a comment of up to 64k
+    let last = zip.len().checked_sub(22)?;
+    let end = (last.saturating_sub(0xffff)..=last)
+        .rev()
+        .find(|&at| zip[at..].starts_with(b"PK\x05\x06"))?;
+    let count = u16_at(zip, end + 10)?;
+    let offset = u32_at(zip, end + 16)?;
+    if count != 0xffff && offset != 0xffff_ffff {
+        return Some((offset, count));
+    }
+
+    // the zip64 locator comes right before, pointing at the zip64 record
+    let locator = end.checked_sub(20)?;
+    if !zip[locator..].starts_with(b"PK\x06\x07") {
+        return None;
+    }
+    let record = u64_at(zip, locator + 8)?;
+    if !zip.get(record..)?.starts_with(b"PK\x06\x06") {
+        return None;
+    }
+    Some((u64_at(zip, record + 48)?, u64_at(zip, record + 32)?))
+}
+
+// the zip64 extended information in an entry's extra field
+fn zip64(mut extra: &[u8]) -> Option<&[u8]> {
+    loop {
+        let id = u16_at(extra, 0)?;
+        let len = u16_at(extra, 2)?;
+        let data = extra.get(4..4 + len)?;
+        if id == 0x0001 {
+            return Some(data);
+        }
+        extra = &extra[4 + len..];
+    }
+}
+
+// the file called `name` in `zip`, looked up in the central directory
+fn entry<'a>(zip: &'a [u8], name: &str) -> Option<Entry<'a>> {
+    let (mut at, count) = directory(zip)?;
+
+    for _ in 0..count {
+        if !zip.get(at..)?.starts_with(b"PK\x01\x02") {
+            return None;
+        }
+        let method = u16_at(zip, at + 10)?;
+        let mut size = u32_at(zip, at + 20)?;
+        let mut uncompressed = u32_at(zip, at + 24)?;
+        let name_len = u16_at(zip, at + 28)?;
+        let extra_len = u16_at(zip, at + 30)?;
+        let comment_len = u16_at(zip, at + 32)?;
+        let mut offset = u32_at(zip, at + 42)?;
+        let entry_name = zip.get(at + 46..at + 46 + name_len)?;
+        let extra = zip.get(at + 46 + name_len..at + 46 + name_len + extra_len)?;
+        at += 46 + name_len + extra_len + comment_len;
+        if entry_name != name.as_bytes() {
+            continue;
+        }
+
+        // values that don't fit 32 bits are in the zip64 field, in this order
+        let mut values = zip64(extra).unwrap_or_default();
+        for value in [&mut uncompressed, &mut size, &mut offset] {
+            if *value == 0xffff_ffff {
+                *value = u64_at(values, 0)?;
+                values = &values[8..];
+            }
+        }
+
+        // the local header has its own name and extra field before the data
+        let start = offset + 30 + u16_at(zip, offset + 26)? + u16_at(zip, offset + 28)?;
+        let data = zip.get(start..start + size)?;
+        return match method {
+            0 => Some(Entry {
+                deflated: false,
+                data,
+            }),
+            8 => Some(Entry {
+                deflated: true,
+                data,
+            }),
+            _ => None,
+        };
+    }
+    None
+}
+
+// decompress raw deflate data with the browser's `DecompressionStream`
+async fn inflate(data: &[u8]) -> Option<String> {
+    let constructor: Function =
+        Reflect::get(&leptos::window(), &JsValue::from_str("DecompressionStream"))
+            .ok()?
+            .dyn_into()
+            .ok()?;
+    let args = Array::of1(&JsValue::from_str("deflate-raw"));
+    // a transform stream, which is a readable and writable pair
+    let stream: ReadableWritablePair = Reflect::construct(&constructor, &args)
+        .ok()?
+        .unchecked_into();
+    let blob = Blob::new_with_u8_array_sequence(&Array::of1(&Uint8Array::from(data))).ok()?;
+    let response =
+        Response::new_with_opt_readable_stream(Some(&blob.stream().pipe_through(&stream))).ok()?;
+    JsFuture::from(response.text().ok()?)
+        .await
+        .ok()?
+        .as_string()
+}
+
+// value of attribute `name` on the first `element`, whatever its namespace
+// prefix
+fn attribute<'a>(xml: &'a str, element: &str, name: &str) -> Option<&'a str> {
+    let mut rest = xml;
+    let tag = loop {
+        rest = &rest[rest.find('<')? + 1..];
+        let tag = &rest[..rest.find('>')?];
+        let tag_name = tag.split(|c: char| c.is_whitespace() || c == '/').next()?;
+        if tag_name.rsplit(':').next() == Some(element) {
+            break tag;
+        }
+    };
+
+    for (at, _) in tag.match_indices(name) {
+        // the whole name, not the end of another
+        if !tag[..at].ends_with(char::is_whitespace) {
+            continue;
+        }
+        let Some(value) = tag[at + name.len()..].trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let quote = value.chars().next().filter(|&q| q == '"' || q == '\'')?;
+        let value = &value[1..];
+        return value.find(quote).map(|end| &value[..end]);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a zip of `files`, as (name, method, data), ending in `comment`, with
+    // every size and offset moved to zip64 fields when `zip64` is set
+    fn zip(files: &[(&str, u16, &[u8])], comment: &[u8], zip64: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut directory = Vec::new();
+        for &(name, method, data) in files {
+            let offset = out.len();
+            out.extend(b"PK\x03\x04");
+            out.extend([20, 0, 0, 0]);
+            out.extend(method.to_le_bytes());
+            out.extend([0; 8]);
+            out.extend((data.len() as u32).to_le_bytes());
+            out.extend((data.len() as u32).to_le_bytes());
+            out.extend((name.len() as u16).to_le_bytes());
+            out.extend(0u16.to_le_bytes());
+            out.extend(name.as_bytes());
+            out.extend(data);
+
+            let (size, at) = match zip64 {
+                true => (0xffff_ffff, 0xffff_ffff),
+                false => (data.len() as u32, offset as u32),
+            };
+            directory.extend(b"PK\x01\x02");
+            directory.extend([20, 0, 20, 0, 0, 0]);
+            directory.extend(method.to_le_bytes());
+            directory.extend([0; 8]);
+            directory.extend(size.to_le_bytes());
+            directory.extend(size.to_le_bytes());
+            directory.extend((name.len() as u16).to_le_bytes());
+            directory.extend((if zip64 { 28u16 } else { 0 }).to_le_bytes());
+            directory.extend([0; 10]);
+            directory.extend(at.to_le_bytes());
+            directory.extend(name.as_bytes());
+            if zip64 {
+                directory.extend(1u16.to_le_bytes());
+                directory.extend(24u16.to_le_bytes());
+                directory.extend((data.len() as u64).to_le_bytes());
+                directory.extend((data.len() as u64).to_le_bytes());
+                directory.extend((offset as u64).to_le_bytes());
+            }
+        }
+
+        let start = out.len();
+        out.extend(&directory);
+        let (count, at) = match zip64 {
+            true => {
+                let record = out.len();
+                out.extend(b"PK\x06\x06");
+                out.extend(44u64.to_le_bytes());
+                out.extend([45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+                out.extend((files.len() as u64).to_le_bytes());
+                out.extend((files.len() as u64).to_le_bytes());
+                out.extend((directory.len() as u64).to_le_bytes());
+                out.extend((start as u64).to_le_bytes());
+                out.extend(b"PK\x06\x07");
+                out.extend(0u32.to_le_bytes());
+                out.extend((record as u64).to_le_bytes());
+                out.extend(1u32.to_le_bytes());
+                (0xffff, 0xffff_ffff)
+            }
+            false => (files.len() as u16, start as u32),
+        };
+        out.extend(b"PK\x05\x06");
+        out.extend([0; 4]);
+        out.extend(count.to_le_bytes());
+        out.extend(count.to_le_bytes());
+        out.extend((directory.len() as u32).to_le_bytes());
+        out.extend(at.to_le_bytes());
+        out.extend((comment.len() as u16).to_le_bytes());
+        out.extend(comment);
+        out
+    }
+
+    const FILES: &[(&str, u16, &[u8])] = &[
+        ("mimetype", 0, b"application/epub+zip"),
+        ("META-INF/container.xml", 8, b"\x01\x02\x03"),
+        ("OEBPS/content.opf", 0, b"<package/>"),
+    ];
+
+    #[test]
+    fn stored_and_deflated_entries() {
+        let zip = zip(FILES, b"", false);
+        assert_eq!(
+            entry(&zip, "OEBPS/content.opf"),
+            Some(Entry {
+                deflated: false,
+                data: b"<package/>",
+            })
+        );
+        assert_eq!(
+            entry(&zip, "META-INF/container.xml"),
+            Some(Entry {
+                deflated: true,
+                data: b"\x01\x02\x03",
+            })
+        );
+        assert_eq!(entry(&zip, "content.opf"), None);
+    }
+
+    #[test]
+    fn trailing_comment() {
+        let zip = zip(FILES, b"made by hand", false);
+        assert_eq!(
+            entry(&zip, "mimetype").map(|e| e.data),
+            Some(&b"application/epub+zip"[..])
+        );
+    }
+
+    #[test]
+    fn zip64_entries() {
+        let zip = zip(FILES, b"", true);
+        assert_eq!(directory(&zip).map(|(_, count)| count), Some(3));
+        assert_eq!(
+            entry(&zip, "OEBPS/content.opf"),
+            Some(Entry {
+                deflated: false,
+                data: b"<package/>",
+            })
+        );
+    }
+
+    #[test]
+    fn unsupported_or_broken() {
+        let bzip2 = zip(&[("a.opf", 12, b"bzip2")], b"", false);
+        assert_eq!(entry(&bzip2, "a.opf"), None);
+        let truncated = zip(FILES, b"", false);
+        let truncated = &truncated[..truncated.len() - 1];
+        assert_eq!(entry(truncated, "mimetype"), None);
+        assert_eq!(entry(b"not a zip", "mimetype"), None);
+    }
+
+    #[test]
+    fn attributes() {
+        let container = r#"<?xml version="1.0"?>
+            <container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+              <rootfiles>
+                <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+              </rootfiles>
+            </container>"#;
+        assert_eq!(
+            attribute(container, "rootfile", "full-path"),
+            Some("OEBPS/content.opf")
+        );
+
+        let spine = |tag| attribute(tag, "spine", "page-progression-direction");
+        assert_eq!(
+            spine(r#"<opf:spine page-progression-direction="rtl">"#),
+            Some("rtl")
+        );
+        assert_eq!(
+            spine("<spine toc='ncx' page-progression-direction = 'rtl'>"),
+            Some("rtl")
+        );
+        // not the end of another attribute's name
+        assert_eq!(
+            spine(r#"<spine x-page-progression-direction="ltr" page-progression-direction="rtl">"#),
+            Some("rtl")
+        );
+        assert_eq!(spine(r#"<spine toc="ncx">"#), None);
+        assert_eq!(
+            spine(r#"<spineitem page-progression-direction="rtl">"#),
+            None
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use std::{cell::RefCell, rc::Rc};
+
+use leptos::*;
+use serde_json::{Map, Value};
+
+use crate::{
+    book::Book,
+    config::{self, Config},
+    storage::{self, Key},
+    theme,
+};
+
+/// Settings that change how a book is rendered, which the book can override,
+/// named as in `Config::to_json`. Those in a group are overridden together,
+/// as colors only mean something with the custom theme.
+pub const GROUPS: [&[&str]; 4] = [
+    &["theme", "colors"],
+    &["layout"],
+    &["spread"],
+    &["typography"],
+];
+
+// fields overridden along with `field`, none if it can't be overridden
+fn group(field: &str) -> &'static [&'static str] {
+    GROUPS
+        .into_iter()
+        .find(|group| group.contains(&field))
+        .unwrap_or(&[])
+}
+
+/// Settings the open book overrides, over the global config.
+#[derive(Clone, Copy)]
+pub struct Overrides {
+    // overridden fields, as `Config::to_json` writes them
+    fields: RwSignal<Map<String, Value>>,
+    config: StoredValue<Rc<RefCell<Config>>>,
+    // notified when the global config changes
+    changed: Trigger,
+    book: ReadSignal<Book>,
+    settings: Memo<Config>,
+}
+
+impl Overrides {
+    /// The settings the open book is rendered with, which follow the book
+    /// as another one opens.
+    pub fn settings(&self) -> Memo<Config> {
+        self.settings
+    }
+
+    /// Pick up changes made to the global config without `update`.
+    pub fn refresh(&self) {
+        self.changed.notify();
+    }
+
+    /// Whether the open book overrides `field`, and the fields grouped with
+    /// it.
+    pub fn overrides(&self, field: &str) -> bool {
+        self.fields.with(|fields| fields.contains_key(field))
+    }
+
+    /// Start overriding `field` and the fields grouped with it with their
+    /// global values, or stop overriding them.
+    pub fn set_overrides(&self, field: &str, overrides: bool) {
+        if overrides == self.fields.with_untracked(|f| f.contains_key(field)) {
+            return;
+        }
+        let global = self.config.with_value(|c| c.borrow().to_value());
+        self.fields.update(|fields| {
+            for &field in group(field) {
+                match overrides {
+                    true => fields.insert(field.to_owned(), global[field].clone()),
+                    false => fields.remove(field),
+                };
+            }
+        });
+        self.save();
+    }
+
+    /// Change settings through `f`, only for the open book if it overrides
+    /// `field` and for every book otherwise.
+    pub fn update(&self, field: &str, f: impl FnOnce(&mut Config)) {
+        if !self
+            .fields
+            .with_untracked(|fields| fields.contains_key(field))
+        {
+            self.config.with_value(|c| {
+                f(&mut c.borrow_mut());
+                c.borrow().save();
+            });
+            self.changed.notify();
+        } else {
+            let mut config = self.settings.get_untracked();
+            f(&mut config);
+            let value = config.to_value();
+            self.fields.update(|fields| {
+                for &field in group(field) {
+                    fields.insert(field.to_owned(), value[field].clone());
+                }
+            });
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let Some(book) = self.book.get_untracked() else {
+            return;
+        };
+        let key = Key::Overrides(book.identifier());
+        self.fields.with_untracked(|fields| {
+            if fields.is_empty() {
+                storage::get().remove(key);
+            } else {
+                storage::get().set_json(key, fields);
+            }
+        });
+    }
+}
+
+fn load(id: &str, config: &Config) -> Map<String, Value> {
+    let Some(mut fields) = storage::get().get_json::<Map<String, Value>>(Key::Overrides(id)) else {
+        return Map::new();
+    };
+    fields.retain(|field, _| !group(field).is_empty());
+    // a group is overridden whole, with the global value for what's missing
+    let global = config.to_value();
+    for group in GROUPS {
+        if group.iter().any(|&field| fields.contains_key(field)) {
+            for &field in group {
+                fields.entry(field).or_insert_with(|| global[field].clone());
+            }
+        }
+    }
+    fields
+}
+
+// the global config with `fields` over it
+fn merge(config: &Config, fields: &Map<String, Value>) -> Config {
+    if fields.is_empty() {
+        return config.clone();
+    }
+    let Value::Object(mut merged) = config.to_value() else {
+        return config.clone();
+    };
+    merged.extend(fields.clone());
+    Config::from_value(&Value::Object(merged)).unwrap_or_else(|| config.clone())
+}
+
+pub fn init() {
+    let book = expect_context::<ReadSignal<Book>>();
+    let fields = create_rw_signal(Map::new());
+    let config = store_value(config::get());
+    let changed = create_trigger();
+    let settings = create_memo(move |_| {
+        changed.track();
+        let config = config.get_value();
+        let config = config.borrow();
+        // without a book there is nothing to override
+        match book.with(Option::is_some) {
+            true => fields.with(|fields| merge(&config, fields)),
+            false => config.clone(),
+        }
+    });
+    let overrides = Overrides {
+        fields,
+        config,
+        changed,
+        book,
+        settings,
+    };
+    provide_context(overrides);
+
+    // the open book's overrides, none without a book
+    create_effect(move |_| {
+        let config = overrides.config.get_value();
+        let fields = book.with(|book| {
+            book.as_ref()
+                .map(|b| load(b.identifier(), &config.borrow()))
+        });
+        overrides.fields.set(fields.unwrap_or_default());
+    });
+    // and color the page the way the book wants
+    create_effect(move |_| settings.with(Config::apply_theme));
+    // keep following the system preference as it changes
+    theme::on_system_change(move || settings.with_untracked(Config::apply_theme));
+}
+
+pub fn get() -> Overrides {
+    expect_context::<Overrides>()
+}
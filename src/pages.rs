@@ -0,0 +1,103 @@
+// Paginated layout: the chapter is laid out in columns as wide as its
+// container, which is scrolled sideways a screen at a time.
+
+use leptos::*;
+use wasm_bindgen::JsCast as _;
+
+/// Space between columns, in pixels.
+pub const GAP: i32 = 48;
+
+/// Element the current chapter is paginated in, if paginated at all.
+pub fn container() -> Option<web_sys::HtmlElement> {
+    document()
+        .query_selector("[data-paginated]")
+        .ok()??
+        .dyn_into()
+        .ok()
+}
+
+// distance between the starts of consecutive screens
+fn step(el: &web_sys::HtmlElement) -> i32 {
+    el.client_width() + GAP
+}
+
+// pages run right to left
+fn rtl(el: &web_sys::HtmlElement) -> bool {
+    el.dir() == "rtl"
+}
+
+fn count(el: &web_sys::HtmlElement) -> usize {
+    let step = step(el);
+    ((el.scroll_width() + GAP + step - 1) / step).max(1) as usize
+}
+
+fn current(el: &web_sys::HtmlElement) -> usize {
+    // scrolling is negative when pages run right to left
+    let offset = el.scroll_left().abs() as f64;
+    (offset / step(el) as f64).round() as usize
+}
+
+fn go(el: &web_sys::HtmlElement, screen: usize) {
+    let offset = screen as i32 * step(el);
+    el.set_scroll_left(if rtl(el) { -offset } else { offset });
+}
+
+/// Turn a screen forward or back, `false` if there's none left that way.
+pub fn turn(forward: bool) -> bool {
+    let Some(el) = container() else {
+        return false;
+    };
+    let current = current(&el);
+    let to = match forward {
+        true if current + 1 < count(&el) => current + 1,
+        false if current > 0 => current - 1,
+        _ => return false,
+    };
+    go(&el, to);
+    true
+}
+
+pub fn first() {
+    if let Some(el) = container() {
+        go(&el, 0);
+    }
+}
+
+pub fn last() {
+    if let Some(el) = container() {
+        go(&el, count(&el) - 1);
+    }
+}
+
+/// Whether the start of `node` is scrolled out of view, above or on an
+/// earlier screen.
+pub fn starts_before_view(node: &web_sys::Element) -> bool {
+    let inner = node.get_bounding_client_rect();
+    let Some(el) = container() else {
+        return inner.top() < -1.0;
+    };
+    let outer = el.get_bounding_client_rect();
+    match rtl(&el) {
+        true => inner.right() > outer.right() + 1.0,
+        false => inner.left() < outer.left() - 1.0,
+    }
+}
+
+/// Bring `node` into view, turning to the screen it starts on when
+/// paginated.
+pub fn reveal(node: &web_sys::Element) {
+    let Some(el) = container() else {
+        node.scroll_into_view();
+        return;
+    };
+    let (outer, inner) = (
+        el.get_bounding_client_rect(),
+        node.get_bounding_client_rect(),
+    );
+    let distance = match rtl(&el) {
+        true => outer.right() - inner.right(),
+        false => inner.left() - outer.left(),
+    };
+    let offset = current(&el) as i32 * step(&el) + distance as i32;
+    go(&el, (offset.max(0) / step(&el)) as usize);
+}
@@ -0,0 +1,132 @@
+use lepu::Epub;
+
+use crate::{book, export::Format, input::Action, theme::Theme};
+
+// commands understood after `:`, with a description of their argument
+const COMMANDS: &[(&str, &str)] = &[
+    ("chapter", "{number|name}"),
+    ("goto", "{percent}%"),
+    ("mark", "{letter}"),
+    ("theme", "{system|light|sepia|dark|black|custom}"),
+    ("search", "{text}"),
+    ("export", "{notes|json}"),
+    ("help", ""),
+];
+
+// the command `name` stands for, which may be any unambiguous prefix
+fn command(name: &str) -> Result<&'static str, String> {
+    let mut found = COMMANDS
+        .iter()
+        .map(|(c, _)| *c)
+        .filter(|c| c.starts_with(name));
+    match (found.next(), found.next()) {
+        (Some(c), None) => Ok(c),
+        (Some(_), Some(_)) if !name.is_empty() => Err(format!("ambiguous command \"{name}\"")),
+        _ => Err(format!("unknown command \"{name}\"")),
+    }
+}
+
+fn usage(name: &str) -> String {
+    let args = COMMANDS
+        .iter()
+        .find(|(c, _)| *c == name)
+        .map_or("", |(_, args)| args);
+    format!("usage: {name} {args}")
+}
+
+/// Parse a command line (without the leading `:`) into the action it runs.
+pub fn parse(line: &str, book: &Epub) -> Result<Action, String> {
+    let line = line.trim();
+    let (name, arg) = line.split_once(' ').unwrap_or((line, ""));
+    let name = command(name)?;
+    let arg = arg.trim();
+
+    let action = match name {
+        "chapter" => match arg.parse::<usize>() {
+            // 1-based, like counts before `G`
+            Ok(n) if n > 0 => Some(Action::GoToPage(n - 1)),
+            Ok(_) => None,
+            Err(_) if arg.is_empty() => None,
+            Err(_) => {
+                let page =
+                    chapter(book, arg).ok_or_else(|| format!("no chapter named \"{arg}\""))?;
+                Some(Action::GoToPage(page))
+            }
+        },
+        "goto" => arg
+            .strip_suffix('%')
+            .and_then(|p| p.trim().parse::<f64>().ok())
+            .filter(|p| (0.0..=100.0).contains(p))
+            .map(|p| Action::GoToPercent(p / 100.0)),
+        "mark" => {
+            let mut chars = arg.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_alphabetic() => Some(Action::SetMark(c)),
+                _ => None,
+            }
+        }
+        "theme" => Theme::parse(arg).map(Action::SetTheme),
+        "search" => (!arg.is_empty()).then(|| Action::Search {
+            query: arg.to_owned(),
+            backwards: false,
+        }),
+        "export" => match arg {
+            "" | "notes" => Some(Action::Export(Format::Markdown)),
+            "json" => Some(Action::Export(Format::Json)),
+            _ => None,
+        },
+        "help" => Some(Action::Help),
+        _ => None,
+    };
+    action.ok_or_else(|| usage(name))
+}
+
+// page of the chapter called `name`, preferring an exact match
+fn chapter(book: &Epub, name: &str) -> Option<usize> {
+    let name = name.to_lowercase();
+    let chapters = book::chapters(book);
+    chapters
+        .iter()
+        .find(|(c, _)| c.to_lowercase() == name)
+        .or_else(|| {
+            chapters
+                .iter()
+                .find(|(c, _)| c.to_lowercase().contains(&name))
+        })
+        .map(|(_, page)| *page)
+}
+
+/// Complete command lines starting like `line`.
+pub fn complete(line: &str, book: &Epub) -> Vec<String> {
+    let Some((name, arg)) = line.split_once(' ') else {
+        return COMMANDS
+            .iter()
+            .filter(|(c, _)| c.starts_with(line))
+            .map(|(c, _)| format!("{c} "))
+            .collect();
+    };
+    let Ok(name) = command(name) else {
+        return Vec::new();
+    };
+    let arg = arg.trim_start().to_lowercase();
+
+    let options: Vec<String> = match name {
+        "chapter" => {
+            let chapters = book::chapters(book).into_iter().map(|(c, _)| c);
+            // names starting with the argument come first
+            let (mut first, rest): (Vec<_>, Vec<_>) = chapters
+                .filter(|c| c.to_lowercase().contains(&arg))
+                .partition(|c| c.to_lowercase().starts_with(&arg));
+            first.extend(rest);
+            first
+        }
+        "theme" => Theme::ALL.iter().map(|t| t.name().to_owned()).collect(),
+        "export" => vec!["notes".to_owned(), "json".to_owned()],
+        _ => Vec::new(),
+    };
+    options
+        .into_iter()
+        .filter(|o| name == "chapter" || o.starts_with(&arg))
+        .map(|o| format!("{name} {o}"))
+        .collect()
+}
@@ -6,11 +6,104 @@ use std::{
 
 use leptos::*;
 use leptos_router::use_navigate;
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::{closure::Closure, JsCast as _, JsValue};
+use web_sys::js_sys::Date;
 
-use crate::{book::Book, config};
+use crate::{
+    book::{self, Book},
+    config::{self, Layout},
+    content, overrides, pages,
+    storage::{self, Key},
+};
+
+/// Position recorded by setting a mark.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Mark {
+    pub page: usize,
+    pub para: usize,
+    /// Milliseconds since the epoch, zero if unknown.
+    #[serde(default)]
+    pub time: f64,
+}
+
+pub type Marks = Rc<RefCell<BTreeMap<char, Mark>>>;
+
+/// Mark that can be followed from any book.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GlobalMark {
+    pub book: String,
+    pub page: usize,
+    pub para: usize,
+    #[serde(default)]
+    pub time: f64,
+}
+
+pub type GlobalMarks = Rc<RefCell<BTreeMap<char, GlobalMark>>>;
+
+/// Named position within a book.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    pub page: usize,
+    pub para: usize,
+    pub time: f64,
+}
 
-pub type Marks = Rc<RefCell<BTreeMap<char, (usize, usize)>>>;
+pub type Bookmarks = Rc<RefCell<Vec<Bookmark>>>;
+
+/// First paragraph in view when the book was last read, kept while other
+/// views are shown.
+#[derive(Clone, Copy)]
+pub struct LastVisible(RwSignal<Option<(usize, usize)>>);
+
+impl LastVisible {
+    pub fn get(&self) -> Option<(usize, usize)> {
+        self.0.get_untracked()
+    }
+}
+
+/// Positions left by non-sequential jumps, walked with `JumpList::back` and
+/// `JumpList::forward`.
+#[derive(Default)]
+pub struct JumpList {
+    entries: Vec<(usize, usize)>,
+    // index of the entry last jumped to, `entries.len()` when not walking
+    idx: usize,
+}
+
+impl JumpList {
+    fn push(&mut self, from: (usize, usize)) {
+        self.entries.truncate(self.idx);
+        if self.entries.last() != Some(&from) {
+            self.entries.push(from);
+        }
+        self.idx = self.entries.len();
+    }
+
+    fn back(&mut self, current: (usize, usize)) -> Option<(usize, usize)> {
+        // remember where the walk started so it can be returned to
+        if self.idx == self.entries.len() {
+            self.push(current);
+            self.idx = self.entries.len() - 1;
+        }
+        if self.idx == 0 {
+            return None;
+        }
+        self.idx -= 1;
+        self.entries.get(self.idx).copied()
+    }
+
+    fn forward(&mut self) -> Option<(usize, usize)> {
+        if self.idx + 1 >= self.entries.len() {
+            return None;
+        }
+        self.idx += 1;
+        self.entries.get(self.idx).copied()
+    }
+}
+
+pub type Jumps = Rc<RefCell<JumpList>>;
 
 pub fn init() {
     // selected page
@@ -19,122 +112,435 @@ pub fn init() {
     let (pos, set_pos) = create_signal(BTreeMap::<usize, usize>::new());
     // set of marks
     let marks: Marks = Rc::new(RefCell::new(BTreeMap::new()));
+    let global_marks: GlobalMarks = Rc::new(RefCell::new(load_global_marks()));
+    let bookmarks: Bookmarks = Rc::new(RefCell::new(Vec::new()));
+    let last_visible = LastVisible(create_rw_signal(None));
+    let jumps: Jumps = Rc::new(RefCell::new(JumpList::default()));
 
     provide_context(page);
     provide_context(set_page);
     provide_context(pos);
     provide_context(set_pos);
-    provide_context(marks);
+    provide_context(marks.clone());
+    provide_context(global_marks);
+    provide_context(bookmarks.clone());
+    provide_context(last_visible);
+    provide_context(jumps.clone());
 
-    // hook: when book changes, load the saved position
+    // hook: when book changes, load the saved position and marks
     let book = expect_context::<ReadSignal<Book>>();
     create_effect(move |_| {
+        let Some(book) = book.get() else { return };
+        // positions from a previously opened book don't apply
+        set_page.set(0);
+        set_pos.set(BTreeMap::new());
+        *marks.borrow_mut() = load_marks(book.identifier());
+        *bookmarks.borrow_mut() = load_bookmarks(book.identifier());
+        last_visible.0.set(None);
+        *jumps.borrow_mut() = JumpList::default();
         if !config::get().borrow().save_position {
             return;
         }
-        let Ok(Some(storage)) = leptos::window().local_storage() else {
-            return;
-        };
-        let Some(book) = book.get() else { return };
-        let id = book.identifier();
-        let Ok(Some(saved_pos)) = storage.get_item(id) else {
-            return;
-        };
-        let Some((page, para)) = saved_pos.split_once(':') else {
+        let Some((page, para)) = saved(book.identifier()) else {
             return;
         };
-        let page = page.parse::<usize>().unwrap();
-        let para = para.parse::<usize>().unwrap();
         set_page.set(page);
         set_pos.update(|pos| _ = pos.insert(page, para));
     });
 }
 
+/// The saved `(page, para)` position for a book.
+pub fn saved(id: &str) -> Option<(usize, usize)> {
+    storage::get().position(id)
+}
+
+fn load_marks(id: &str) -> BTreeMap<char, Mark> {
+    storage::get().get_json(Key::Marks(id)).unwrap_or_default()
+}
+
+pub fn save_marks(id: &str, marks: &BTreeMap<char, Mark>) {
+    storage::get().set_json(Key::Marks(id), marks);
+}
+
+fn load_global_marks() -> BTreeMap<char, GlobalMark> {
+    storage::get()
+        .get_json(Key::GlobalMarks)
+        .unwrap_or_default()
+}
+
+pub fn save_global_marks(marks: &BTreeMap<char, GlobalMark>) {
+    storage::get().set_json(Key::GlobalMarks, marks);
+}
+
+fn load_bookmarks(id: &str) -> Vec<Bookmark> {
+    storage::get()
+        .get_json(Key::Bookmarks(id))
+        .unwrap_or_default()
+}
+
+pub fn save_bookmarks(id: &str, bookmarks: &[Bookmark]) {
+    storage::get().set_json(Key::Bookmarks(id), &bookmarks);
+}
+
+/// Jump to a global mark, opening its book first if it isn't open.
+pub fn follow_global(mark: GlobalMark) {
+    let book = expect_context::<ReadSignal<Book>>();
+    let GlobalMark {
+        book: id,
+        page,
+        para,
+        ..
+    } = mark;
+    let open = book.with_untracked(|b| b.as_ref().map(|b| b.identifier() == id));
+    if open == Some(true) {
+        jump_to(page, para);
+    } else {
+        book::open(id, move || jump_to(page, para));
+    }
+}
+
+/// Move to `para` of `page`, scrolling it into view once rendered.
+pub fn jump_to(page: usize, para: usize) {
+    record_jump();
+    go_to(page, para);
+}
+
+fn go_to(page: usize, para: usize) {
+    let set_pos = expect_context::<WriteSignal<BTreeMap<usize, usize>>>();
+    set_pos.update(move |pos| _ = pos.insert(page, para));
+    (use_navigate())(&page.to_string(), Default::default());
+}
+
+/// `marks` of the book `id` along with the global marks set in it.
+pub fn book_marks(id: &str, marks: &Marks, global_marks: &GlobalMarks) -> BTreeMap<char, Mark> {
+    let mut all = marks.borrow().clone();
+    let global = global_marks.borrow();
+    all.extend(
+        global
+            .iter()
+            .filter(|(_, mark)| mark.book == id)
+            .map(|(&name, mark)| {
+                let (page, para, time) = (mark.page, mark.para, mark.time);
+                (name, Mark { page, para, time })
+            }),
+    );
+    all
+}
+
+// where the reader currently is, as far as the saved positions know
+fn current() -> (usize, usize) {
+    let page = expect_context::<ReadSignal<usize>>().get_untracked();
+    let pos = expect_context::<ReadSignal<BTreeMap<usize, usize>>>();
+    let para = pos.with_untracked(|pos| pos.get(&page).copied().unwrap_or(0));
+    (page, para)
+}
+
+/// Add the current position to the jump list, before leaving it.
+pub fn record_jump() {
+    let from = current();
+    expect_context::<Jumps>().borrow_mut().push(from);
+}
+
+/// Return to the position before the last jump.
+pub fn jump_back() {
+    let to = expect_context::<Jumps>().borrow_mut().back(current());
+    if let Some((page, para)) = to {
+        go_to(page, para);
+    }
+}
+
+/// Undo a `jump_back`.
+pub fn jump_forward() {
+    let to = expect_context::<Jumps>().borrow_mut().forward();
+    if let Some((page, para)) = to {
+        go_to(page, para);
+    }
+}
+
+// element of a paragraph rendered by `chapter_to_html`
+fn paragraph(page: usize, para: usize) -> Option<web_sys::Element> {
+    document().get_element_by_id(&content::para_id(page, para))
+}
+
+// the paragraph `offset` paragraphs away from `(page, para)`, going on into
+// other pages if they're rendered too
+fn paragraph_from(page: usize, para: usize, offset: isize) -> Option<web_sys::Element> {
+    let from = paragraph(page, para)?;
+    let all = document().query_selector_all("[data-para]").ok()?;
+    let idx =
+        (0..all.length()).find(|&i| all.get(i).is_some_and(|n| n.is_same_node(Some(&from))))?;
+    let to = u32::try_from(idx as isize + offset).ok()?;
+    all.get(to)?.dyn_into().ok()
+}
+
 pub struct Tracker {
     obs: web_sys::IntersectionObserver,
-    first_visible: Memo<Option<usize>>,
+    first_visible: Memo<Option<(usize, usize)>>,
     book: ReadSignal<Book>,
     pos: ReadSignal<BTreeMap<usize, usize>>,
-    set_pos: WriteSignal<BTreeMap<usize, usize>>,
     page: ReadSignal<usize>,
     marks: Marks,
+    global_marks: GlobalMarks,
+    // open the next chapter rendered on its last screen, after paging back
+    // into it
+    at_end: StoredValue<bool>,
+    // several pages are rendered, one after the other
+    continuous: Memo<bool>,
 }
 
 impl Tracker {
-    pub fn track(&self, node: HtmlElement<html::Div>, id: usize, page: usize) {
+    pub fn track(&self, node: HtmlElement<html::Div>, page: usize, para: usize) {
         self.obs.observe(&node);
+        // only the page being opened, not ones rendered after or before it
+        if page != self.page.get_untracked() {
+            return;
+        }
         self.pos.with_untracked(move |pos| {
-            if Some(id) == pos.get(&page).copied() && id != 0 {
+            if Some(para) == pos.get(&page).copied() && para != 0 {
                 create_effect(move |_| {
-                    node.scroll_into_view();
+                    pages::reveal(&node);
                 });
             }
         });
     }
 
-    pub fn first_visible(&self) -> Option<usize> {
+    /// `(page, para)` of the first paragraph in view.
+    pub fn first_visible(&self) -> Option<(usize, usize)> {
         self.first_visible.get()
     }
 
+    /// Current `(page, para)` the reader is at.
+    pub fn position(&self) -> (usize, usize) {
+        self.first_visible().unwrap_or((self.page.get(), 0))
+    }
+
+    /// Lowercase marks are local to the book, uppercase marks are global.
     pub fn set_mark(&self, name: char) {
-        let Some(para) = self.first_visible() else {
+        let Some((page, para)) = self.first_visible() else {
             return;
         };
-        let page = self.page.get();
-        self.marks.borrow_mut().insert(name, (page, para));
+        let Some(book) = self.book.get_untracked() else {
+            return;
+        };
+        let time = Date::now();
+        if name.is_ascii_uppercase() {
+            let mut marks = self.global_marks.borrow_mut();
+            let book = book.identifier().to_owned();
+            marks.insert(
+                name,
+                GlobalMark {
+                    book,
+                    page,
+                    para,
+                    time,
+                },
+            );
+            save_global_marks(&marks);
+        } else {
+            let mut marks = self.marks.borrow_mut();
+            marks.insert(name, Mark { page, para, time });
+            save_marks(book.identifier(), &marks);
+        }
     }
 
     pub fn follow_mark(&self, name: char) {
-        if let Some((page, para)) = self.marks.borrow().get(&name).copied() {
-            self.set_pos.update(move |pos| _ = pos.insert(page, para));
-            (use_navigate())(&page.to_string(), Default::default());
+        if name.is_ascii_uppercase() {
+            let mark = self.global_marks.borrow().get(&name).cloned();
+            if let Some(mark) = mark {
+                follow_global(mark);
+            }
+        } else if let Some(mark) = self.marks.borrow().get(&name).copied() {
+            jump_to(mark.page, mark.para);
         }
     }
 
-    pub fn move_to_next_page(&self) {
+    /// Move `count` pages forward, stopping at the last one.
+    pub fn move_to_next_page(&self, count: usize) {
         let id = self.page.get();
         let max_id = self.book.get().unwrap().document_count();
-        if id + 1 < max_id {
-            (use_navigate())(&(id + 1).to_string(), Default::default());
+        let to = id.saturating_add(count).min(max_id.saturating_sub(1));
+        if to > id {
+            (use_navigate())(&to.to_string(), Default::default());
         }
     }
 
-    pub fn move_to_previous_page(&self) {
+    /// Move `count` pages back, stopping at the first one.
+    pub fn move_to_previous_page(&self, count: usize) {
         let id = self.page.get();
-        if id > 0 {
-            (use_navigate())(&(id - 1).to_string(), Default::default());
+        let to = id.saturating_sub(count);
+        if to < id {
+            (use_navigate())(&to.to_string(), Default::default());
+        }
+    }
+
+    /// Turn `count` screens forward when paginated, moving on to the next
+    /// page past the last screen. Moves `count` pages otherwise.
+    pub fn next_page(&self, count: usize) {
+        if pages::container().is_none() {
+            return self.move_to_next_page(count);
+        }
+        for _ in 0..count {
+            if !pages::turn(true) {
+                return self.move_to_next_page(1);
+            }
+        }
+    }
+
+    /// Like `next_page`, backwards, ending up on the last screen of the
+    /// previous page.
+    pub fn previous_page(&self, count: usize) {
+        if pages::container().is_none() {
+            return self.move_to_previous_page(count);
+        }
+        for _ in 0..count {
+            if !pages::turn(false) {
+                if self.page.get_untracked() > 0 {
+                    self.at_end.set_value(true);
+                }
+                return self.move_to_previous_page(1);
+            }
+        }
+    }
+
+    /// Called once a page has been rendered.
+    pub fn rendered(&self) {
+        if self.at_end.get_value() {
+            self.at_end.set_value(false);
+            pages::last();
+        }
+    }
+
+    /// Bring the first visible paragraph back into view, e.g. after the
+    /// layout changed.
+    pub fn reveal_first_visible(&self) {
+        let first_visible = self.first_visible.get_untracked();
+        if let Some(node) = first_visible.and_then(|(page, para)| paragraph(page, para)) {
+            pages::reveal(&node);
+        }
+    }
+
+    /// Jump to the start of `page`, or of the last page if it's past the end.
+    pub fn go_to_page(&self, page: usize) {
+        let Some(book) = self.book.get_untracked() else {
+            return;
+        };
+        let to = page.min(book.document_count().saturating_sub(1));
+        if to != self.page.get_untracked() {
+            jump_to(to, 0);
+        }
+    }
+
+    /// Jump to the page `fraction` of the way through the book.
+    pub fn go_to_percent(&self, fraction: f64) {
+        let Some(book) = self.book.get_untracked() else {
+            return;
+        };
+        let page = (fraction * book.document_count() as f64) as usize;
+        self.go_to_page(page);
+    }
+
+    pub fn scroll_to_top(&self) {
+        record_jump();
+        if self.continuous.get_untracked() {
+            let (page, _) = self.position();
+            if let Some(node) = content::page_element(page) {
+                node.scroll_into_view();
+            }
+            return;
+        }
+        leptos::window().scroll_to_with_x_and_y(0.0, 0.0);
+        pages::first();
+    }
+
+    pub fn scroll_to_bottom(&self) {
+        record_jump();
+        if self.continuous.get_untracked() {
+            let (page, _) = self.position();
+            if let Some(node) = content::page_element(page) {
+                node.scroll_into_view_with_bool(false);
+            }
+            return;
+        }
+        let height = document().body().map_or(0, |body| body.scroll_height());
+        leptos::window().scroll_to_with_x_and_y(0.0, height as f64);
+        pages::last();
+    }
+
+    /// Scroll by `screens` times the window height, negative being up.
+    /// Paginated, every started screen is a whole one.
+    pub fn scroll_screens(&self, screens: f64) {
+        if pages::container().is_some() {
+            for _ in 0..screens.abs().ceil() as usize {
+                pages::turn(screens > 0.0);
+            }
+            return;
+        }
+        let window = leptos::window();
+        let height = window
+            .inner_height()
+            .ok()
+            .and_then(|h| h.as_f64())
+            .unwrap_or(0.0);
+        window.scroll_by_with_x_and_y(0.0, height * screens);
+    }
+
+    /// Bring the paragraph `count` after the first visible one to the top.
+    pub fn next_paragraph(&self, count: usize) {
+        let (page, para) = self.position();
+        match paragraph_from(page, para, count as isize) {
+            Some(node) => pages::reveal(&node),
+            // past the last paragraph
+            None => self.scroll_screens(1.0),
+        }
+    }
+
+    /// Bring the paragraph `count` before the first visible one to the top,
+    /// counting the first visible one if its start is scrolled past.
+    pub fn previous_paragraph(&self, count: usize) {
+        let (page, para) = self.position();
+        let cut_off = paragraph(page, para).is_some_and(|node| pages::starts_before_view(&node));
+        let offset = if cut_off { count - 1 } else { count };
+        match paragraph_from(page, para, -(offset as isize)) {
+            Some(node) => pages::reveal(&node),
+            None => {
+                leptos::window().scroll_to_with_x_and_y(0.0, 0.0);
+                pages::first();
+            }
         }
     }
 
     pub fn init() -> Self {
-        let (vs, set_vs) = create_signal(BTreeSet::<usize>::new());
-        let first_visible = create_memo(move |_| vs.get().iter().min().copied());
+        let (vs, set_vs) = create_signal(BTreeSet::<(usize, usize)>::new());
+        let first_visible = create_memo(move |_| vs.get().first().copied());
 
         let book = expect_context::<ReadSignal<Book>>();
         let page = expect_context::<ReadSignal<usize>>();
         let marks = expect_context::<Marks>();
+        let global_marks = expect_context::<GlobalMarks>();
         let pos = expect_context::<ReadSignal<BTreeMap<usize, usize>>>();
         let set_pos = expect_context::<WriteSignal<BTreeMap<usize, usize>>>();
+        let last_visible = expect_context::<LastVisible>();
+
+        let config = config::get();
+        let settings = overrides::get().settings();
+        let continuous = create_memo(move |_| settings.with(|s| s.layout == Layout::Continuous));
 
         create_effect(move |prev| {
             let cur = page.get();
-            if prev != Some(cur) {
+            // continuously, pages stay rendered while the current one changes
+            if prev != Some(cur) && !continuous.get_untracked() {
                 set_vs.set(Default::default());
             }
             cur
         });
 
-        let config = config::get();
         create_effect(move |_| {
-            let Some(para) = first_visible.get() else {
-                return;
-            };
-            let Ok(Some(storage)) = leptos::window().local_storage() else {
+            let Some((page, para)) = first_visible.get() else {
                 return;
             };
             let Some(book) = book.get() else { return };
-            let page = page.get();
             let id = book.identifier();
+            last_visible.0.set(Some((page, para)));
             set_pos.update(move |pos| {
                 if para > 1 {
                     pos.insert(page, para);
@@ -143,14 +549,16 @@ impl Tracker {
                 }
             });
             if config.borrow().save_position {
-                let _ = storage.set_item(id, &format!("{page}:{para}"));
+                storage::get().set_position(id, (page, para));
             }
         });
 
         let cb = move |entries, _| {
             for entry in entries {
                 let entry = web_sys::IntersectionObserverEntry::from(entry);
-                let id = entry.target().id().parse::<usize>().unwrap();
+                let Some(id) = content::parse_para_id(&entry.target().id()) else {
+                    continue;
+                };
                 match entry.is_intersecting() {
                     true => set_vs.update(|vs| _ = vs.insert(id)),
                     false => set_vs.update(|vs| _ = vs.remove(&id)),
@@ -172,9 +580,11 @@ impl Tracker {
             first_visible,
             book,
             pos,
-            set_pos,
             page,
             marks,
+            global_marks,
+            at_end: store_value(false),
+            continuous,
         }
     }
 }
@@ -0,0 +1,157 @@
+use leptos::*;
+use lepu::{Content, Epub};
+
+use crate::{book::Book, content::plain_text, position};
+
+// characters of context kept on either side of a match
+const CONTEXT: usize = 40;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub page: usize,
+    pub para: usize,
+    /// Character offsets of the match within the paragraph's text.
+    pub start: usize,
+    pub end: usize,
+    pub before: String,
+    pub matched: String,
+    pub after: String,
+}
+
+#[derive(Clone, Copy)]
+pub struct Search {
+    pub query: RwSignal<String>,
+    pub results: RwSignal<Vec<Match>>,
+    /// Index into `results` of the match last jumped to.
+    pub current: RwSignal<Option<usize>>,
+    /// Direction `step` moves in when not reversed.
+    pub backwards: RwSignal<bool>,
+}
+
+impl Search {
+    pub fn run(&self, book: &Epub, query: String) {
+        self.results.set(find(book, &query));
+        self.query.set(query);
+        self.current.set(None);
+        self.backwards.set(false);
+    }
+
+    /// Jump to the closest match after (or before, when searching backwards)
+    /// the `(page, para)` position, wrapping around the book.
+    pub fn step(&self, from: (usize, usize), reverse: bool) {
+        let backwards = self.backwards.get_untracked() != reverse;
+        let idx = self.results.with_untracked(|results| {
+            if results.is_empty() {
+                return None;
+            }
+            // continue from the last match if it's still on screen
+            let from = match self.current.get_untracked().and_then(|i| results.get(i)) {
+                Some(m) if m.page == from.0 => (m.page, m.para, m.start),
+                _ if backwards => (from.0, from.1, usize::MAX),
+                _ => (from.0, from.1, 0),
+            };
+            let key = |m: &Match| (m.page, m.para, m.start);
+            if backwards {
+                let idx = results.partition_point(|m| key(m) < from);
+                Some(idx.checked_sub(1).unwrap_or(results.len() - 1))
+            } else {
+                let idx = results.partition_point(|m| key(m) <= from);
+                Some(if idx < results.len() { idx } else { 0 })
+            }
+        });
+        if let Some(idx) = idx {
+            self.go(idx);
+        }
+    }
+
+    /// Jump to the `idx`th result, highlighting it.
+    pub fn go(&self, idx: usize) {
+        let Some(m) = self.results.with_untracked(|r| r.get(idx).cloned()) else {
+            return;
+        };
+        self.current.set(Some(idx));
+        position::jump_to(m.page, m.para);
+    }
+
+    /// The highlighted match, if it is within `page`.
+    pub fn highlighted(&self, page: usize) -> Option<Match> {
+        let idx = self.current.get()?;
+        self.results
+            .with(|r| r.get(idx).cloned())
+            .filter(|m| m.page == page)
+    }
+}
+
+pub fn init() {
+    let search = Search {
+        query: create_rw_signal(String::new()),
+        results: create_rw_signal(Vec::new()),
+        current: create_rw_signal(None),
+        backwards: create_rw_signal(false),
+    };
+    provide_context(search);
+
+    // results are only meaningful for the book they came from
+    let book = expect_context::<ReadSignal<Book>>();
+    create_effect(move |_| {
+        book.track();
+        search.query.set(String::new());
+        search.results.set(Vec::new());
+        search.current.set(None);
+    });
+}
+
+pub fn get() -> Search {
+    expect_context::<Search>()
+}
+
+/// Case-insensitive search for `query` across every document in the book.
+pub fn find(book: &Epub, query: &str) -> Vec<Match> {
+    let needle: Vec<char> = query.chars().map(fold).collect();
+    let mut matches = Vec::new();
+    if needle.is_empty() {
+        return matches;
+    }
+
+    for page in 0..book.document_count() {
+        let mut para = 0;
+        let _ = book.traverse_chapter(page, |_, content, _| {
+            if let Content::Textual(text) = content {
+                let chars: Vec<char> = plain_text(&text).chars().collect();
+                let folded: Vec<char> = chars.iter().copied().map(fold).collect();
+                let mut start = 0;
+                while start + needle.len() <= folded.len() {
+                    if folded[start..start + needle.len()] != needle[..] {
+                        start += 1;
+                        continue;
+                    }
+                    let end = start + needle.len();
+                    let collect = |from: usize, to: usize| {
+                        chars[from..to]
+                            .iter()
+                            .map(|&c| if c == '\n' { ' ' } else { c })
+                            .collect::<String>()
+                    };
+                    matches.push(Match {
+                        page,
+                        para,
+                        start,
+                        end,
+                        before: collect(start.saturating_sub(CONTEXT), start),
+                        matched: collect(start, end),
+                        after: collect(end, (end + CONTEXT).min(chars.len())),
+                    });
+                    start = end;
+                }
+            }
+            para += 1;
+        });
+    }
+
+    matches
+}
+
+// lowercase a character without changing the character count
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
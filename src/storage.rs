@@ -0,0 +1,404 @@
+// Everything kept between sessions, other than the books themselves (see
+// `library`), goes through here. Keys are prefixed so they can't collide with
+// anything else the origin stores, and so clearing wepu's data leaves the
+// rest alone.
+//
+// keys after the prefix (non-normative)
+// "config" => json object of settings, see `Config::to_json`
+// "last-opened" => "{book identifier}" (most recently opened book, data lives in the indexeddb library)
+// "global-marks" => json object of global marks, "{name}" => { book, page, para, time }
+// "book/{book identifier}/position" => "{page}:{para}"
+// "book/{book identifier}/highlights" => json array of highlights
+// "book/{book identifier}/marks" => json object of marks, "{name}" => { page, para, time }
+// "book/{book identifier}/bookmarks" => json array of bookmarks, { name, page, para, time }
+// "book/{book identifier}/overrides" => json object of the settings the book overrides, as in "config"
+// "legacy/{key}" => what older versions stored under "{key}", until it is migrated
+
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use leptos::spawn_local;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::library;
+
+pub const PREFIX: &str = "wepu:";
+
+/// Somewhere to keep strings by key.
+pub trait Backend {
+    fn get(&self, key: &str) -> Option<String>;
+    fn set(&self, key: &str, value: &str);
+    fn remove(&self, key: &str);
+    /// Every key in the backend, wepu's or not.
+    fn keys(&self) -> Vec<String>;
+}
+
+/// The browser's local storage.
+pub struct Local(web_sys::Storage);
+
+impl Local {
+    pub fn new() -> Option<Self> {
+        leptos::window().local_storage().ok()?.map(Local)
+    }
+}
+
+impl Backend for Local {
+    fn get(&self, key: &str) -> Option<String> {
+        self.0.get_item(key).ok()?
+    }
+
+    fn set(&self, key: &str, value: &str) {
+        let _ = self.0.set_item(key, value);
+    }
+
+    fn remove(&self, key: &str) {
+        let _ = self.0.remove_item(key);
+    }
+
+    fn keys(&self) -> Vec<String> {
+        (0..self.0.length().unwrap_or(0))
+            .filter_map(|i| self.0.key(i).ok()?)
+            .collect()
+    }
+}
+
+/// Kept in memory, gone on reload.
+#[derive(Default)]
+pub struct Memory(RefCell<BTreeMap<String, String>>);
+
+impl Backend for Memory {
+    fn get(&self, key: &str) -> Option<String> {
+        self.0.borrow().get(key).cloned()
+    }
+
+    fn set(&self, key: &str, value: &str) {
+        self.0.borrow_mut().insert(key.to_owned(), value.to_owned());
+    }
+
+    fn remove(&self, key: &str) {
+        self.0.borrow_mut().remove(key);
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.0.borrow().keys().cloned().collect()
+    }
+}
+
+/// IndexedDB, for browsers that won't give out local storage. Everything is
+/// read up front so lookups needn't wait, and changes are written in the
+/// background.
+pub struct Indexed(Memory);
+
+impl Indexed {
+    pub async fn open() -> Option<Self> {
+        let settings = library::settings().await.ok()?;
+        Some(Indexed(Memory(RefCell::new(
+            settings.into_iter().collect(),
+        ))))
+    }
+}
+
+impl Backend for Indexed {
+    fn get(&self, key: &str) -> Option<String> {
+        self.0.get(key)
+    }
+
+    fn set(&self, key: &str, value: &str) {
+        self.0.set(key, value);
+        let (key, value) = (key.to_owned(), value.to_owned());
+        spawn_local(async move {
+            let _ = library::put_setting(&key, &value).await;
+        });
+    }
+
+    fn remove(&self, key: &str) {
+        self.0.remove(key);
+        let key = key.to_owned();
+        spawn_local(async move {
+            let _ = library::remove_setting(&key).await;
+        });
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.0.keys()
+    }
+}
+
+/// What is stored, by book for what differs between books.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key<'a> {
+    Config,
+    LastOpened,
+    GlobalMarks,
+    Position(&'a str),
+    Highlights(&'a str),
+    Marks(&'a str),
+    Bookmarks(&'a str),
+    Overrides(&'a str),
+    /// Stored by an older version under this name.
+    Legacy(&'static str),
+}
+
+impl Key<'_> {
+    fn name(self) -> String {
+        let name = match self {
+            Key::Config => "config".to_owned(),
+            Key::LastOpened => "last-opened".to_owned(),
+            Key::GlobalMarks => "global-marks".to_owned(),
+            Key::Position(id) => format!("book/{id}/position"),
+            Key::Highlights(id) => format!("book/{id}/highlights"),
+            Key::Marks(id) => format!("book/{id}/marks"),
+            Key::Bookmarks(id) => format!("book/{id}/bookmarks"),
+            Key::Overrides(id) => format!("book/{id}/overrides"),
+            Key::Legacy(name) => format!("legacy/{name}"),
+        };
+        format!("{PREFIX}{name}")
+    }
+}
+
+/// Where wepu keeps its data, whatever the backend.
+#[derive(Clone)]
+pub struct Storage(Rc<dyn Backend>);
+
+impl Storage {
+    pub fn new(backend: impl Backend + 'static) -> Self {
+        Self(Rc::new(backend))
+    }
+
+    pub fn get(&self, key: Key) -> Option<String> {
+        self.0.get(&key.name())
+    }
+
+    pub fn set(&self, key: Key, value: &str) {
+        self.0.set(&key.name(), value);
+    }
+
+    pub fn remove(&self, key: Key) {
+        self.0.remove(&key.name());
+    }
+
+    /// `None` if missing or not a `T`.
+    pub fn get_json<T: DeserializeOwned>(&self, key: Key) -> Option<T> {
+        serde_json::from_str(&self.get(key)?).ok()
+    }
+
+    pub fn set_json(&self, key: Key, value: &impl Serialize) {
+        if let Ok(json) = serde_json::to_string(value) {
+            self.set(key, &json);
+        }
+    }
+
+    /// The saved `(page, para)` position in a book.
+    pub fn position(&self, id: &str) -> Option<(usize, usize)> {
+        parse_position(&self.get(Key::Position(id))?)
+    }
+
+    pub fn set_position(&self, id: &str, (page, para): (usize, usize)) {
+        self.set(Key::Position(id), &format!("{page}:{para}"));
+    }
+
+    /// Remove everything wepu stored, and nothing else.
+    pub fn clear(&self) {
+        for key in self.0.keys() {
+            if key.starts_with(PREFIX) {
+                self.0.remove(&key);
+            }
+        }
+    }
+
+    /// Move what older versions stored at the root, unprefixed, to where it
+    /// is kept now: the keys wepu always used, and those named after the
+    /// books in `ids`. Anything else at the root isn't wepu's.
+    pub fn migrate(&self, ids: &[String]) {
+        for (old, key) in [
+            ("config", Key::Config),
+            ("l", Key::LastOpened),
+            ("g", Key::GlobalMarks),
+            // left for `config` and `book` to read in their old formats
+            ("c", Key::Legacy("c")),
+            ("k", Key::Legacy("k")),
+            ("t", Key::Legacy("t")),
+            ("s", Key::Legacy("s")),
+            ("b", Key::Legacy("b")),
+        ] {
+            self.move_from(old, key);
+        }
+        for id in ids {
+            self.migrate_book(id);
+        }
+    }
+
+    /// `migrate` for a single book, run as it opens since books that were
+    /// never kept in the library aren't known before.
+    pub fn migrate_book(&self, id: &str) {
+        for (old, key) in [
+            (id.to_owned(), Key::Position(id)),
+            (format!("{id}:h"), Key::Highlights(id)),
+            (format!("{id}:m"), Key::Marks(id)),
+            (format!("{id}:b"), Key::Bookmarks(id)),
+            (format!("{id}:o"), Key::Overrides(id)),
+        ] {
+            self.move_from(&old, key);
+        }
+    }
+
+    // move what's at the root under `old` to `key`
+    fn move_from(&self, old: &str, key: Key) {
+        let Some(value) = self.0.get(old) else {
+            return;
+        };
+        // what's already been moved is newer
+        if self.get(key).is_none() {
+            self.set(key, &value);
+        }
+        self.0.remove(old);
+    }
+}
+
+fn parse_position(s: &str) -> Option<(usize, usize)> {
+    let (page, para) = s.split_once(':')?;
+    Some((page.parse().ok()?, para.parse().ok()?))
+}
+
+thread_local! {
+    static STORAGE: RefCell<Option<Storage>> = const { RefCell::new(None) };
+}
+
+/// Settle on a backend: local storage, IndexedDB where that's unavailable, or
+/// memory if neither is. Runs before the app is mounted.
+pub async fn init() {
+    let storage = match Local::new() {
+        Some(local) => Storage::new(local),
+        None => match Indexed::open().await {
+            Some(indexed) => Storage::new(indexed),
+            None => Storage::new(Memory::default()),
+        },
+    };
+    // books are known by the library, as is the one last opened
+    let mut ids: Vec<String> = library::entries().await.into_iter().map(|e| e.id).collect();
+    ids.extend(storage.0.get("l"));
+    storage.migrate(&ids);
+    STORAGE.with(|s| *s.borrow_mut() = Some(storage));
+}
+
+pub fn get() -> Storage {
+    STORAGE.with(|s| {
+        s.borrow()
+            .clone()
+            .expect("storage is initialized before the app is mounted")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory() -> (Rc<Memory>, Storage) {
+        let memory = Rc::new(Memory::default());
+        (memory.clone(), Storage(memory))
+    }
+
+    #[test]
+    fn prefixed() {
+        let (memory, storage) = memory();
+        storage.set(Key::Config, "{}");
+        storage.set_position("c", (3, 4));
+        assert_eq!(memory.keys(), ["wepu:book/c/position", "wepu:config"]);
+        assert_eq!(storage.get(Key::Config).as_deref(), Some("{}"));
+        assert_eq!(storage.position("c"), Some((3, 4)));
+        assert_eq!(storage.position("d"), None);
+    }
+
+    #[test]
+    fn json() {
+        let (_, storage) = memory();
+        storage.set_json(Key::Marks("a"), &vec![1, 2, 3]);
+        assert_eq!(
+            storage.get_json::<Vec<u32>>(Key::Marks("a")),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(storage.get_json::<String>(Key::Marks("a")), None);
+        assert_eq!(storage.get_json::<Vec<u32>>(Key::Marks("b")), None);
+    }
+
+    #[test]
+    fn clear_leaves_other_keys() {
+        let (memory, storage) = memory();
+        memory.set("theirs", "1");
+        storage.set(Key::Config, "{}");
+        storage.set(Key::Highlights("a"), "[]");
+        storage.clear();
+        assert_eq!(memory.keys(), ["theirs"]);
+    }
+
+    #[test]
+    fn migrate() {
+        let (memory, storage) = memory();
+        for (key, value) in [
+            ("config", "{}"),
+            ("c", "true:false"),
+            ("l", "urn:isbn:1"),
+            ("g", "{}"),
+            ("b", "base64"),
+            ("urn:isbn:1", "5:12"),
+            ("urn:isbn:1:h", "[]"),
+            ("urn:isbn:1:m", "{}"),
+            ("urn:isbn:1:b", "[]"),
+            ("urn:isbn:1:o", "{}"),
+            // another app's, even if they look like wepu's
+            ("theirs", "1"),
+            ("their-id", "3:4"),
+            ("their-id:h", "[]"),
+            ("wepu:config", "{\"version\":1}"),
+        ] {
+            memory.set(key, value);
+        }
+        storage.migrate(&["urn:isbn:1".to_owned()]);
+        assert_eq!(
+            memory.keys(),
+            [
+                "their-id",
+                "their-id:h",
+                "theirs",
+                "wepu:book/urn:isbn:1/bookmarks",
+                "wepu:book/urn:isbn:1/highlights",
+                "wepu:book/urn:isbn:1/marks",
+                "wepu:book/urn:isbn:1/overrides",
+                "wepu:book/urn:isbn:1/position",
+                "wepu:config",
+                "wepu:global-marks",
+                "wepu:last-opened",
+                "wepu:legacy/b",
+                "wepu:legacy/c",
+            ]
+        );
+        assert_eq!(storage.position("urn:isbn:1"), Some((5, 12)));
+        assert_eq!(storage.get(Key::LastOpened).as_deref(), Some("urn:isbn:1"));
+        assert_eq!(storage.get(Key::Legacy("c")).as_deref(), Some("true:false"));
+        assert_eq!(storage.get(Key::Config).as_deref(), Some("{\"version\":1}"));
+        // nothing left to move the second time
+        storage.migrate(&["urn:isbn:1".to_owned()]);
+        assert_eq!(memory.keys().len(), 13);
+    }
+
+    #[test]
+    fn migrate_book_outside_library() {
+        let (memory, storage) = memory();
+        memory.set("urn:isbn:2", "7:3");
+        memory.set("urn:isbn:2:h", "[]");
+        memory.set("theirs", "1");
+        // only books in the library are known up front
+        storage.migrate(&[]);
+        assert_eq!(storage.position("urn:isbn:2"), None);
+
+        storage.migrate_book("urn:isbn:2");
+        assert_eq!(storage.position("urn:isbn:2"), Some((7, 3)));
+        assert_eq!(
+            memory.keys(),
+            [
+                "theirs",
+                "wepu:book/urn:isbn:2/highlights",
+                "wepu:book/urn:isbn:2/position",
+            ]
+        );
+    }
+}
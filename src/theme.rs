@@ -0,0 +1,160 @@
+use leptos::{document, wasm_bindgen::JsCast as _, web_sys};
+use serde::{Deserialize, Serialize};
+
+use crate::config::lenient;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    /// Sepia or dark, following the system's preference.
+    System,
+    Light,
+    Sepia,
+    Dark,
+    Black,
+    /// Colors picked by the reader.
+    Custom,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 6] = [
+        Theme::System,
+        Theme::Light,
+        Theme::Sepia,
+        Theme::Dark,
+        Theme::Black,
+        Theme::Custom,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::System => "system",
+            Theme::Light => "light",
+            Theme::Sepia => "sepia",
+            Theme::Dark => "dark",
+            Theme::Black => "black",
+            Theme::Custom => "custom",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Theme> {
+        Theme::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    /// Colors of the theme, `custom` being those of `Theme::Custom`.
+    pub fn colors(self, custom: &Colors) -> Colors {
+        match self {
+            Theme::System if prefers_dark() => Theme::Dark.colors(custom),
+            Theme::System => Theme::Sepia.colors(custom),
+            Theme::Light => Colors::new("#ffffff", "#18181b"),
+            Theme::Sepia => Colors::default(),
+            Theme::Dark => Colors::new("#09090b", "#f4f4f5"),
+            Theme::Black => Colors::new("#000000", "#d4d4d8"),
+            Theme::Custom => custom.clone(),
+        }
+    }
+}
+
+/// Colors of a theme, as `#rrggbb`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Colors {
+    #[serde(deserialize_with = "or_default::background")]
+    pub background: String,
+    #[serde(deserialize_with = "or_default::text")]
+    pub text: String,
+    #[serde(deserialize_with = "or_default::link")]
+    pub link: String,
+    /// Search matches, drawn translucent over the text.
+    #[serde(deserialize_with = "or_default::highlight")]
+    pub highlight: String,
+}
+
+lenient!(or_default for Colors {
+    background: String where |c| is_color(c),
+    text: String where |c| is_color(c),
+    link: String where |c| is_color(c),
+    highlight: String where |c| is_color(c),
+});
+
+impl Default for Colors {
+    fn default() -> Self {
+        Colors::new("#f2e2c9", "#34281c")
+    }
+}
+
+impl Colors {
+    // links and highlights are the same sky blue in every built in theme
+    fn new(background: &str, text: &str) -> Self {
+        Self {
+            background: background.to_owned(),
+            text: text.to_owned(),
+            link: "#0ea5e9".to_owned(),
+            highlight: "#0ea5e9".to_owned(),
+        }
+    }
+
+    /// Read colors stored before `Config::to_json`:
+    /// `{background}:{text}:{link}:{highlight}`
+    pub fn from_legacy(s: &str) -> Option<Self> {
+        let mut fields = s.split(':').map(|c| is_color(c).then(|| c.to_owned()));
+        Some(Self {
+            background: fields.next()??,
+            text: fields.next()??,
+            link: fields.next()??,
+            highlight: fields.next()??,
+        })
+    }
+
+    // a dark background, which the `dark:` variants of tailwind classes
+    // are meant for
+    fn dark(&self) -> bool {
+        if !is_color(&self.background) {
+            return false;
+        }
+        let channel =
+            |i: usize| u8::from_str_radix(&self.background[i..i + 2], 16).unwrap_or(0) as u32;
+        let (r, g, b) = (channel(1), channel(3), channel(5));
+        // perceived brightness
+        (r * 299 + g * 587 + b * 114) / 1000 < 128
+    }
+}
+
+/// Whether `s` is a `#rrggbb` color.
+pub fn is_color(s: &str) -> bool {
+    s.len() == 7 && s.starts_with('#') && s[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+const DARK_QUERY: &str = "(prefers-color-scheme: dark)";
+
+pub fn prefers_dark() -> bool {
+    matches!(leptos::window().match_media(DARK_QUERY), Ok(Some(q)) if q.matches())
+}
+
+/// Color the page in `theme`, through css variables on the root element
+/// that tailwind's `page`, `ink`, `link` and `highlight` colors refer to.
+pub fn apply(theme: Theme, custom: &Colors) {
+    let colors = theme.colors(custom);
+    let Some(root) = document().document_element() else {
+        return;
+    };
+    let _ = root.class_list().toggle_with_force("dark", colors.dark());
+    let Ok(root) = root.dyn_into::<web_sys::HtmlElement>() else {
+        return;
+    };
+    let style = root.style();
+    let _ = style.set_property("--bg", &colors.background);
+    let _ = style.set_property("--fg", &colors.text);
+    let _ = style.set_property("--link", &colors.link);
+    // translucent, so highlighted text stays readable
+    let _ = style.set_property("--highlight", &format!("{}66", colors.highlight));
+}
+
+/// Call `f` whenever the system switches between light and dark.
+pub fn on_system_change(f: impl Fn() + 'static) {
+    if let Ok(Some(query)) = leptos::window().match_media(DARK_QUERY) {
+        let cb = leptos::wasm_bindgen::closure::Closure::<dyn Fn()>::new(f);
+        query.set_onchange(Some(cb.as_ref().unchecked_ref()));
+        cb.forget();
+    }
+}
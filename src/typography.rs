@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::lenient;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Font {
+    /// Crimson Pro
+    Serif,
+    /// Inter
+    Sans,
+    SystemSerif,
+    SystemSans,
+    Mono,
+}
+
+impl Font {
+    pub const ALL: [Font; 5] = [
+        Font::Serif,
+        Font::Sans,
+        Font::SystemSerif,
+        Font::SystemSans,
+        Font::Mono,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Font::Serif => "serif",
+            Font::Sans => "sans",
+            Font::SystemSerif => "system-serif",
+            Font::SystemSans => "system-sans",
+            Font::Mono => "mono",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Font::Serif => "Crimson Pro",
+            Font::Sans => "Inter",
+            Font::SystemSerif => "System serif",
+            Font::SystemSans => "System sans-serif",
+            Font::Mono => "Monospace",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Font> {
+        Font::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    // css font family and weight
+    fn css(self) -> (&'static str, u16) {
+        match self {
+            // regular crimson pro is rather heavy
+            Font::Serif => ("'Crimson Pro', serif", 300),
+            Font::Sans => ("Inter, sans-serif", 400),
+            Font::SystemSerif => ("Georgia, 'Times New Roman', serif", 400),
+            Font::SystemSans => ("system-ui, sans-serif", 400),
+            Font::Mono => ("ui-monospace, monospace", 400),
+        }
+    }
+}
+
+/// How the text of a book is set.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Typography {
+    #[serde(deserialize_with = "or_default::font")]
+    pub font: Font,
+    /// Percentage of the size the screen width calls for.
+    #[serde(deserialize_with = "or_default::scale")]
+    pub scale: u32,
+    #[serde(deserialize_with = "or_default::line_height")]
+    pub line_height: f64,
+    /// Space between paragraphs, in em.
+    #[serde(deserialize_with = "or_default::paragraph_spacing")]
+    pub paragraph_spacing: f64,
+    /// Widest the text gets, in em, or as wide as the page.
+    #[serde(deserialize_with = "or_default::column_width")]
+    pub column_width: Option<u32>,
+    #[serde(deserialize_with = "or_default::justify")]
+    pub justify: bool,
+    #[serde(deserialize_with = "or_default::hyphenate")]
+    pub hyphenate: bool,
+}
+
+lenient!(or_default for Typography {
+    font: Font,
+    scale: u32,
+    line_height: f64,
+    paragraph_spacing: f64,
+    // null for as wide as the page
+    column_width: Option<u32> where |&w| w != Some(0),
+    justify: bool,
+    hyphenate: bool,
+});
+
+impl Default for Typography {
+    fn default() -> Self {
+        Self {
+            font: Font::Serif,
+            scale: 100,
+            line_height: 1.5,
+            paragraph_spacing: 0.75,
+            column_width: None,
+            justify: true,
+            hyphenate: false,
+        }
+    }
+}
+
+impl Typography {
+    /// Inline style of the element text is rendered in. Paragraph spacing
+    /// is left to the children, through `--para-gap`.
+    pub fn style(&self) -> String {
+        let (family, weight) = self.font.css();
+        let mut style = format!(
+            "font-family: {family}; font-weight: {weight}; font-size: {}%; line-height: {}; \
+             --para-gap: {}em; text-align: {}; hyphens: {};",
+            self.scale,
+            self.line_height,
+            self.paragraph_spacing,
+            if self.justify { "justify" } else { "start" },
+            if self.hyphenate { "auto" } else { "manual" },
+        );
+        if let Some(width) = self.column_width {
+            style.push_str(&format!(
+                " max-width: {width}em; margin-left: auto; margin-right: auto;"
+            ));
+        }
+        style
+    }
+
+    /// Read typography stored before `Config::to_json`: `{font}:{scale}:{line
+    /// height}:{paragraph spacing}:{column width, 0 for none}:{justify}:{hyphenate}`
+    pub fn from_legacy(s: &str) -> Option<Self> {
+        let mut fields = s.split(':');
+        let typography = Self {
+            font: Font::parse(fields.next()?)?,
+            scale: fields.next()?.parse().ok()?,
+            line_height: fields.next()?.parse().ok()?,
+            paragraph_spacing: fields.next()?.parse().ok()?,
+            column_width: Some(fields.next()?.parse().ok()?).filter(|&w| w > 0),
+            justify: fields.next()?.parse().ok()?,
+            hyphenate: fields.next()?.parse().ok()?,
+        };
+        Some(typography)
+    }
+}
@@ -2,6 +2,7 @@
 const colors = require('tailwindcss/colors')
 
 module.exports = {
+  darkMode: 'class',
   content: { 
     files: ["*.html", "./src/**/*.rs"],
   },
@@ -21,10 +22,18 @@ module.exports = {
       current: 'currentColor',
       zinc: colors.zinc,
       sky: colors.sky,
+      amber: colors.amber,
+      emerald: colors.emerald,
+      rose: colors.rose,
       'sepia': {
         light: '#F2E2C9',
         dark: '#34281C',
       },
+      // the current theme's, set on the root element
+      page: 'var(--bg)',
+      ink: 'var(--fg)',
+      link: 'var(--link)',
+      highlight: 'var(--highlight)',
     },
     extend: {},
   },